@@ -0,0 +1,157 @@
+use crate::error::RateLimitError;
+use crate::rate_limiter_redis::{
+    grant_key, reset_key, sequence_key, subject_key, RateLimiterRedis,
+};
+use redis::{Commands, ConnectionLike};
+use std::time::Duration;
+
+/// The number of keys SCAN is asked to look at per call.
+const SCAN_COUNT: usize = 1000;
+
+/// Administrative operations, e.g. for support to unblock a subject. The keys are found by SCAN
+/// (never KEYS), so a large Redis is not blocked while they are listed.
+///
+/// On a Redis Cluster SCAN only walks the node it is sent to, so these operations are meant for
+/// a single Redis or a Redis monitored by Sentinel.
+impl<C: ConnectionLike> RateLimiterRedis<C> {
+    /// Clears what every algorithm has recorded for the subject on the resource, in all windows,
+    /// and returns the number of keys deleted. The extra units granted to the subject are kept,
+    /// and so are the markers of the refunds made, so a request is still refunded only once. The
+    /// requests recorded before the reset are not refunded after it, as their units are cleared.
+    pub fn reset_subject(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<u64, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let keys = self
+            .scan(&format!("{}*", escape_pattern(&key)))?
+            .into_iter()
+            .filter(|found| found == &key || found.starts_with(&format!("{key}:")))
+            .collect::<Vec<_>>();
+        self.mark_reset(&keys)?;
+
+        self.delete(&keys)
+    }
+
+    /// Clears what every algorithm has recorded for every subject on the resource, and returns
+    /// the number of keys deleted. The extra units granted to the subjects and the markers of the
+    /// refunds made are kept, and the requests recorded before the reset are not refunded.
+    pub fn reset_resource(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+    ) -> Result<u64, RateLimitError> {
+        let pattern = format!(
+            "{}{{*",
+            escape_pattern(&format!("{key_prefix}:{resource}:"))
+        );
+        let keys = self.scan(&pattern)?;
+        self.mark_reset(&keys)?;
+
+        self.delete(&keys)
+    }
+
+    /// Grants the subject `extra` units on the resource for `ttl`, replacing any earlier grant.
+    /// While the grant lasts it raises the limit of the window algorithms and the capacity of the
+    /// buckets and GCRA, for every rule checked on the subject. The units spent beyond the rule
+    /// are paid back once the grant expires, as the windows roll over or the buckets drain.
+    /// The grant lets more requests through, not larger ones: the cost of a single request is
+    /// still capped by the rule.
+    pub fn grant_extra_quota(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        extra: u64,
+        ttl: Duration,
+    ) -> Result<(), RateLimitError> {
+        let key = grant_key(&subject_key(key_prefix, resource, subject));
+        let ttl = ttl.as_millis() as u64;
+        if ttl == 0 {
+            return Err(RateLimitError::InvalidConfig(
+                "the grant must last at least one millisecond".to_string(),
+            ));
+        }
+
+        self.conn.pset_ex::<_, _, ()>(key, extra, ttl as usize)?;
+
+        Ok(())
+    }
+
+    /// Lists the keys under the key prefix, e.g. to see which subjects are being limited.
+    pub fn active_keys(&mut self, key_prefix: &str) -> Result<Vec<String>, RateLimitError> {
+        self.scan(&format!("{}:*", escape_pattern(key_prefix)))
+    }
+
+    /// Returns the keys matching the pattern, each once, sorted.
+    fn scan(&mut self, pattern: &str) -> Result<Vec<String>, RateLimitError> {
+        let mut scan = redis::cmd("SCAN");
+        scan.cursor_arg(0)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(SCAN_COUNT);
+        let mut keys: Vec<String> = scan.iter(&mut self.conn)?.collect();
+        // a key may be returned more than once while the keyspace is rehashed
+        keys.sort();
+        keys.dedup();
+
+        Ok(keys)
+    }
+
+    /// Marks the subjects of the sequences among the keys as reset at their current receipt, so
+    /// the receipts issued before are not refunded. The marker lasts as long as the sequence,
+    /// which outlives every receipt it issued.
+    fn mark_reset(&mut self, keys: &[String]) -> Result<(), RateLimitError> {
+        for key in keys.iter().filter_map(|key| key.strip_suffix(":seq")) {
+            let sequence = sequence_key(key);
+            let (seq, ttl): (Option<u64>, i64) = redis::pipe()
+                .get(&sequence)
+                .pttl(&sequence)
+                .query(&mut self.conn)?;
+            if let (Some(seq), true) = (seq, ttl > 0) {
+                self.conn
+                    .pset_ex::<_, _, ()>(reset_key(key), seq, ttl as usize)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the keys but the ones which outlive a reset, and returns the number deleted.
+    fn delete(&mut self, keys: &[String]) -> Result<u64, RateLimitError> {
+        let keys = keys.iter().filter(|key| !is_kept(key)).collect::<Vec<_>>();
+        let mut deleted = 0;
+        for chunk in keys.chunks(SCAN_COUNT) {
+            deleted += self.conn.del::<_, u64>(chunk)?;
+        }
+
+        Ok(deleted)
+    }
+}
+
+/// Escapes the characters SCAN MATCH treats as a glob, so they only match themselves.
+fn escape_pattern(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Whether the key outlives a reset: the extra units granted to a subject, the marker of a
+/// refund made, or the sequence of the receipts and the marker of the reset, which the suffix
+/// after the hash tag of the subject tells.
+fn is_kept(key: &str) -> bool {
+    let suffix = key.rsplit_once('}').map_or(key, |(_, suffix)| suffix);
+    suffix == ":grant"
+        || suffix.starts_with(":refund:")
+        || suffix.ends_with(":seq")
+        || suffix.ends_with(":reset")
+}
@@ -0,0 +1,71 @@
+use crate::decision::Decision;
+use crate::error::RateLimitError;
+use crate::rate_limiter::Algorithm;
+use crate::rule::Rule;
+
+/// Where the algorithms keep the requests they have recorded: Redis
+/// ([`RateLimiterRedis`](crate::rate_limiter_redis::RateLimiterRedis), shared by every process
+/// using it) or the memory of this process
+/// ([`RateLimiterMemory`](crate::rate_limiter_memory::RateLimiterMemory)). Both make the same
+/// decisions, so the same limiter code runs distributed, embedded or in tests.
+pub trait Backend {
+    /// Checks a request of the subject on the resource by the algorithm, and records it if
+    /// allowed. See the `record_*` methods of the backends.
+    fn record(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.record_weighted(algorithm, key_prefix, resource, subject, rule, 1)
+    }
+
+    /// Checks a request which costs `cost` units, and records all of them if allowed. See the
+    /// `record_*_weighted` methods of the backends.
+    fn record_weighted(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError>;
+
+    /// Returns the decision for a request of the subject on the resource by the algorithm,
+    /// without recording it. See the `fetch_*` methods of the backends.
+    fn fetch(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError>;
+
+    /// Returns exactly the decision [`Backend::record_weighted`] would make right now for a
+    /// request which costs `cost` units, without recording it or changing any key. See the
+    /// `peek_*` methods of the backends.
+    fn peek(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError>;
+
+    /// Clears what the algorithm has recorded for the subject on the resource. See the
+    /// `reset_*` methods of the backends.
+    fn reset(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError>;
+}
@@ -0,0 +1,59 @@
+use crate::error::RateLimitError;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{self, Duration, SystemTime};
+
+/// The clock of the calling host, read by the limiters when they take the time from the
+/// client (see [`TimeSource::Client`](crate::rate_limiter_redis::TimeSource::Client)) and by
+/// [`RateLimiterMemory`](crate::rate_limiter_memory::RateLimiterMemory).
+pub trait Clock: Send + Sync {
+    /// Returns the current time since the Unix epoch.
+    fn now(&self) -> Result<Duration, RateLimitError>;
+}
+
+/// The system clock of this host.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Result<Duration, RateLimitError> {
+        SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map_err(|err| RateLimitError::Clock(err.to_string()))
+    }
+}
+
+/// A clock which only moves when told to, so the behaviour at the boundaries of windows and
+/// refills can be tested without sleeping. Clones share the same time.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    /// Starts the clock at `now` since the Unix epoch.
+    pub fn new(now: Duration) -> Self {
+        MockClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, by: Duration) {
+        *self.lock() += by;
+    }
+
+    /// Moves the clock to `now` since the Unix epoch, which may be in the past.
+    pub fn set(&self, now: Duration) {
+        *self.lock() = now;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Duration> {
+        self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Result<Duration, RateLimitError> {
+        Ok(*self.lock())
+    }
+}
@@ -0,0 +1,156 @@
+use redis::{ErrorKind, FromRedisValue, RedisError, RedisResult, Value};
+use std::time::{self, Duration, SystemTime};
+
+/// The result of checking a request against a rate limit, the same for every algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    /// Whether the request is allowed (or, when only peeking, would be allowed).
+    pub allowed: bool,
+    /// The number of requests allowed in a window, or the capacity of a bucket.
+    pub limit: u64,
+    /// The number of requests (or units, for weighted requests) which are still allowed after
+    /// this one, or right now when it is denied.
+    pub remaining: u64,
+    /// When the limit is fully available again.
+    pub reset_at: SystemTime,
+    /// How long to wait before a request can be allowed again, zero if this one is allowed.
+    pub retry_after: Duration,
+}
+
+/// Parses the reply of the scripts: `{allowed, limit, remaining, reset_at, retry_after}`,
+/// where `reset_at` is in millis since the Unix epoch and `retry_after` in millis.
+impl FromRedisValue for Decision {
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        let (allowed, limit, remaining, reset_at, retry_after): (bool, u64, u64, u64, u64) =
+            FromRedisValue::from_redis_value(v)?;
+
+        Ok(Decision {
+            allowed,
+            limit,
+            remaining,
+            reset_at: time::UNIX_EPOCH + Duration::from_millis(reset_at),
+            retry_after: Duration::from_millis(retry_after),
+        })
+    }
+}
+
+/// The slot given to a request by the leaky bucket when it shapes the traffic instead of
+/// rejecting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    /// Whether the request got a slot, false if it would have to wait longer than allowed.
+    pub scheduled: bool,
+    /// When the request may be processed, or when to try again if it was not scheduled.
+    pub process_at: SystemTime,
+    /// How long to wait until `process_at`.
+    pub delay: Duration,
+}
+
+/// Parses the reply of the shaping script: `{scheduled, process_at, delay}`, where
+/// `process_at` is in millis since the Unix epoch and `delay` in millis.
+impl FromRedisValue for Schedule {
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        let (scheduled, process_at, delay): (bool, u64, u64) = FromRedisValue::from_redis_value(v)?;
+
+        Ok(Schedule {
+            scheduled,
+            process_at: time::UNIX_EPOCH + Duration::from_millis(process_at),
+            delay: Duration::from_millis(delay),
+        })
+    }
+}
+
+/// A lease on one of the slots of a concurrency limit, held until it is released or expires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    /// The ID of the lease, to release or renew it by.
+    pub id: String,
+    /// When the lease expires if it is not released or renewed before, e.g. because its holder
+    /// crashed.
+    pub expires_at: SystemTime,
+    /// The number of leases which can still be acquired.
+    pub remaining: u64,
+}
+
+/// Parses the reply of the acquiring script: `{id, expires_at, remaining}`, where `expires_at`
+/// is in millis since the Unix epoch.
+impl FromRedisValue for Lease {
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        let (id, expires_at, remaining): (String, u64, u64) = FromRedisValue::from_redis_value(v)?;
+
+        Ok(Lease {
+            id,
+            expires_at: time::UNIX_EPOCH + Duration::from_millis(expires_at),
+            remaining,
+        })
+    }
+}
+
+/// The result of checking a request against several limits at once, e.g. per user, per
+/// organisation and global.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiDecision {
+    /// Whether every limit allows the request. Otherwise it is recorded on none of them.
+    pub allowed: bool,
+    /// The index of the binding limit: the denying one to wait the longest for, or the one with
+    /// the fewest units left when the request is allowed.
+    pub binding: usize,
+    /// The decision of every limit, in the order of the limits. When the request is denied, the
+    /// limits which allow it tell what they would have left after it.
+    pub decisions: Vec<Decision>,
+}
+
+impl MultiDecision {
+    /// The decision of the binding limit.
+    pub fn binding_decision(&self) -> &Decision {
+        &self.decisions[self.binding]
+    }
+
+    /// The decisions of all limits as one: the fewest units left, the latest reset, and how long
+    /// to wait for the binding limit.
+    pub fn combined(&self) -> Decision {
+        let binding = self.binding_decision();
+
+        Decision {
+            allowed: self.allowed,
+            limit: binding.limit,
+            remaining: self
+                .decisions
+                .iter()
+                .map(|decision| decision.remaining)
+                .min()
+                .unwrap_or(binding.remaining),
+            reset_at: self
+                .decisions
+                .iter()
+                .map(|decision| decision.reset_at)
+                .max()
+                .unwrap_or(binding.reset_at),
+            retry_after: binding.retry_after,
+        }
+    }
+}
+
+/// Parses the reply of the script checking several limits: `{allowed, binding}` (the binding
+/// limit counted from one), followed by the reply of every limit like parsed by [`Decision`].
+impl FromRedisValue for MultiDecision {
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        let items: Vec<Value> = FromRedisValue::from_redis_value(v)?;
+        if items.len() < 2 || !(items.len() - 2).is_multiple_of(5) {
+            return Err(RedisError::from((
+                ErrorKind::TypeError,
+                "Response of the wrong size for the decisions of the limits",
+            )));
+        }
+        let binding: usize = FromRedisValue::from_redis_value(&items[1])?;
+
+        Ok(MultiDecision {
+            allowed: FromRedisValue::from_redis_value(&items[0])?,
+            binding: binding.saturating_sub(1),
+            decisions: items[2..]
+                .chunks(5)
+                .map(|decision| Decision::from_redis_value(&Value::Bulk(decision.to_vec())))
+                .collect::<RedisResult<_>>()?,
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use redis::{ErrorKind, RedisError};
+use std::fmt;
+
+/// The errors of the rate limiter, so callers can tell "Redis is down" from "bad config"
+/// from "a key holds something else" and log them in their own way.
+#[derive(Debug)]
+pub enum RateLimitError {
+    /// Could not connect to Redis, or the connection was dropped.
+    Connection(RedisError),
+    /// Redis did not answer in time.
+    Timeout(RedisError),
+    /// A script could not be loaded or failed while running on Redis.
+    Script(RedisError),
+    /// The limiter was given a configuration it cannot work with.
+    InvalidConfig(String),
+    /// A key used by the limiter holds a value of another type.
+    WrongType(RedisError),
+    /// The current time could not be read.
+    Clock(String),
+    /// Any other error reported by Redis or the protocol.
+    Redis(RedisError),
+}
+
+impl RateLimitError {
+    /// Classifies an error returned while loading or running a script.
+    pub(crate) fn from_script(err: RedisError) -> Self {
+        match RateLimitError::from(err) {
+            RateLimitError::Redis(err) => RateLimitError::Script(err),
+            err => err,
+        }
+    }
+
+    /// Classifies an error returned while reading the clock of the Redis server.
+    pub(crate) fn from_clock(err: RedisError) -> Self {
+        match err.kind() {
+            ErrorKind::TypeError => RateLimitError::Clock(err.to_string()),
+            _ => RateLimitError::from(err),
+        }
+    }
+}
+
+impl From<RedisError> for RateLimitError {
+    fn from(err: RedisError) -> Self {
+        if err.is_timeout() {
+            RateLimitError::Timeout(err)
+        } else if err.is_connection_refusal() || err.is_connection_dropped() || err.is_io_error() {
+            RateLimitError::Connection(err)
+        } else if err.code() == Some("WRONGTYPE") {
+            RateLimitError::WrongType(err)
+        } else if err.kind() == ErrorKind::NoScriptError {
+            RateLimitError::Script(err)
+        } else if err.kind() == ErrorKind::InvalidClientConfig {
+            RateLimitError::InvalidConfig(err.to_string())
+        } else {
+            RateLimitError::Redis(err)
+        }
+    }
+}
+
+/// A connection could not be taken from the pool, e.g. the Redis can not be reached before the
+/// connection timeout.
+#[cfg(feature = "pool")]
+impl From<r2d2::Error> for RateLimitError {
+    fn from(err: r2d2::Error) -> Self {
+        let err = std::io::Error::other(err.to_string());
+        RateLimitError::Connection(RedisError::from(err))
+    }
+}
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitError::Connection(err) => write!(f, "could not connect to the Redis: {err}"),
+            RateLimitError::Timeout(err) => write!(f, "timed out waiting for the Redis: {err}"),
+            RateLimitError::Script(err) => write!(f, "could not run the script: {err}"),
+            RateLimitError::InvalidConfig(reason) => write!(f, "invalid configuration: {reason}"),
+            RateLimitError::WrongType(err) => write!(f, "key holds the wrong type: {err}"),
+            RateLimitError::Clock(reason) => write!(f, "could not read the clock: {reason}"),
+            RateLimitError::Redis(err) => write!(f, "error from the Redis: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RateLimitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RateLimitError::Connection(err)
+            | RateLimitError::Timeout(err)
+            | RateLimitError::Script(err)
+            | RateLimitError::WrongType(err)
+            | RateLimitError::Redis(err) => Some(err),
+            RateLimitError::InvalidConfig(_) | RateLimitError::Clock(_) => None,
+        }
+    }
+}
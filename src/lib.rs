@@ -1 +1,21 @@
+mod admin;
+pub mod backend;
+pub mod clock;
+pub mod decision;
+pub mod error;
+pub mod limits;
+mod multi_window;
+#[cfg(feature = "calendar")]
+pub mod quota;
+pub mod rate_limiter;
+pub mod rate_limiter_memory;
 pub mod rate_limiter_redis;
+#[cfg(feature = "async")]
+pub mod rate_limiter_redis_async;
+#[cfg(feature = "pool")]
+pub mod rate_limiter_redis_pool;
+pub mod refund;
+pub mod rule;
+mod semaphore;
+#[cfg(feature = "sentinel")]
+pub mod sentinel;
@@ -0,0 +1,83 @@
+use crate::decision::MultiDecision;
+use crate::error::RateLimitError;
+use crate::rate_limiter::Algorithm;
+use crate::rate_limiter_redis::{route, subject_key, Mode, RateLimiterRedis};
+use crate::rule::Rule;
+use redis::ConnectionLike;
+
+/// One of the limits a request is checked against by [`RateLimiterRedis::record_limits`], e.g.
+/// per user, per organisation, per endpoint or global.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limit {
+    pub algorithm: Algorithm,
+    pub resource: String,
+    pub subject: String,
+    pub rule: Rule,
+}
+
+impl Limit {
+    pub fn new(algorithm: Algorithm, resource: &str, subject: &str, rule: Rule) -> Self {
+        Limit {
+            algorithm,
+            resource: resource.to_string(),
+            subject: subject.to_string(),
+            rule,
+        }
+    }
+}
+
+/// Hierarchical or multi-dimensional limits, checked in one script so a request is recorded on
+/// all of them or on none.
+///
+/// No two limits may be on the same resource and subject, since their algorithms would count on
+/// the same keys; the windows of one subject are checked together by
+/// [`RateLimiterRedis::record_multi_window`].
+///
+/// The keys of all limits must be on the same Redis, so on a Redis Cluster the limits of
+/// different subjects can not be checked together (Redis rejects them with CROSSSLOT).
+impl<C: ConnectionLike> RateLimiterRedis<C> {
+    /// Checks a request which costs `cost` units against every limit, each by its own algorithm,
+    /// and records it on all of them only if all allow it. The decision tells the binding limit,
+    /// i.e. the one which denied the request, or the tightest one.
+    pub fn record_limits(
+        &mut self,
+        key_prefix: &str,
+        limits: &[Limit],
+        cost: u64,
+    ) -> Result<MultiDecision, RateLimitError> {
+        self.limits(key_prefix, limits, cost, Mode::Record)
+    }
+
+    /// Returns exactly the decision [`RateLimiterRedis::record_limits`] would make right now,
+    /// without recording anything.
+    pub fn peek_limits(
+        &mut self,
+        key_prefix: &str,
+        limits: &[Limit],
+        cost: u64,
+    ) -> Result<MultiDecision, RateLimitError> {
+        self.limits(key_prefix, limits, cost, Mode::Peek)
+    }
+
+    fn limits(
+        &mut self,
+        key_prefix: &str,
+        limits: &[Limit],
+        cost: u64,
+        mode: Mode,
+    ) -> Result<MultiDecision, RateLimitError> {
+        let limits: Vec<_> = limits
+            .iter()
+            .map(|limit| {
+                let key = subject_key(key_prefix, &limit.resource, &limit.subject);
+                (limit.algorithm, key, limit.rule)
+            })
+            .collect();
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.scripts
+            .limits(&limits, now, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)
+    }
+}
@@ -1,38 +1,25 @@
-use std::{sync::mpsc::TryRecvError, time::Duration};
+use std::time::Duration;
 
-mod rate_limiter_redis;
+use rrr::{error::RateLimitError, rate_limiter_redis, rule::Rule};
 
-fn main() -> Result<(), ()> {
+fn main() -> Result<(), RateLimitError> {
     let conn = "redis://127.0.0.1:6379/";
-    let limit_count = 1;
+    let limit_count = 2;
     let size = Duration::from_secs(1);
-    let mut client = rate_limiter_redis::RateLimiterRedis::open(conn, limit_count)?;
+    let rule = Rule::new(limit_count, size);
+    let mut client = rate_limiter_redis::RateLimiterRedis::open(conn)?;
     let key_prefix = "test4";
     let resource = "data";
     let subject = "andy";
 
-    // act && assert
-    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    for _ in 0..10 {
+        let decision = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        println!(
+            "allowed: {}, remaining: {}, retry after: {:?}",
+            decision.allowed, decision.remaining, decision.retry_after
+        );
+        std::thread::sleep(Duration::from_millis(200));
+    }
 
-    let _consumer = std::thread::spawn(move || -> Result<(), ()> {
-        loop {
-            println!("Consuming...");
-            client.consume_leaky_bucket(key_prefix, resource, subject, size)?;
-            std::thread::sleep(Duration::from_secs(size.as_secs()));
-            match rx.try_recv() {
-                Ok(_) | Err(TryRecvError::Disconnected) => {
-                    println!("The consumer is terminating...");
-                    break;
-                }
-                Err(TryRecvError::Empty) => {}
-            }
-        }
-        Ok(())
-    });
-
-    std::thread::sleep(Duration::from_secs(1));
-
-    let _ = tx.send(());
-    let _ = _consumer.join();
     Ok(())
 }
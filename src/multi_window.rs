@@ -0,0 +1,85 @@
+use crate::decision::{Decision, MultiDecision};
+use crate::error::RateLimitError;
+use crate::rate_limiter::Algorithm;
+use crate::rate_limiter_redis::{route, subject_key, Mode, RateLimiterRedis};
+use crate::rule::MultiWindowRule;
+use redis::ConnectionLike;
+
+/// Several windows on one subject, e.g. a burst and a sustained limit, checked in one script so
+/// a request is counted in all of them or in none. Every window is counted by the same window
+/// algorithm (fixed window, sliding log or sliding window) on keys of its own.
+impl<C: ConnectionLike> RateLimiterRedis<C> {
+    /// Checks a request against every window of the rule. The decision tells the fewest requests
+    /// left in any window and the latest reset of them.
+    pub fn record_multi_window(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &MultiWindowRule,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        self.multi_window(algorithm, &key, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterRedis::record_multi_window`], for a request which costs `cost` units,
+    /// see [`RateLimiterRedis::record_fixed_window_weighted`].
+    pub fn record_multi_window_weighted(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &MultiWindowRule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        self.multi_window(algorithm, &key, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_multi_window(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &MultiWindowRule,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        self.multi_window(algorithm, &key, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedis::peek_fixed_window`], for
+    /// [`RateLimiterRedis::record_multi_window_weighted`].
+    pub fn peek_multi_window(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &MultiWindowRule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        self.multi_window(algorithm, &key, rule, cost, Mode::Peek)
+    }
+
+    fn multi_window(
+        &mut self,
+        algorithm: Algorithm,
+        key: &str,
+        rule: &MultiWindowRule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let now = self.time_source.script_arg(&*self.clock)?;
+        let decision: MultiDecision = self
+            .scripts
+            .multi_window(algorithm, key, now, rule, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)?;
+
+        Ok(decision.combined())
+    }
+}
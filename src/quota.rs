@@ -0,0 +1,293 @@
+use crate::decision::Decision;
+use crate::error::RateLimitError;
+use crate::rate_limiter_redis::{route, subject_key, Mode, RateLimiterRedis};
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, TimeZone, Timelike, Utc};
+pub use chrono_tz::Tz;
+use redis::{Commands, ConnectionLike};
+use std::fmt;
+use std::time::{self, Duration, SystemTime};
+
+/// The calendar period a [`Quota`] is counted over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalendarPeriod {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl fmt::Display for CalendarPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CalendarPeriod::Hour => "hour",
+            CalendarPeriod::Day => "day",
+            CalendarPeriod::Week => "week",
+            CalendarPeriod::Month => "month",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// A quota counted over calendar periods, e.g. "10000 per month from the 15th" or "100 per day
+/// in the timezone of the customer", unlike the windows of a [`Rule`](crate::rule::Rule) which
+/// are aligned to multiples of their size since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    /// The number of requests allowed in a period.
+    pub limit: u64,
+    pub period: CalendarPeriod,
+    /// The timezone the periods start at midnight (or on the hour) in, UTC if not set. The
+    /// periods follow its daylight saving time, so a day may last 23 or 25 hours.
+    pub timezone: Tz,
+    /// The day the periods start on: the day of the month for monthly quotas (on the last day
+    /// of the shorter months if it is after it, e.g. the billing day of the plan), or the day of
+    /// the week from 1 (Monday) to 7 (Sunday) for weekly ones. 1 if not set, and ignored by
+    /// hourly and daily quotas.
+    pub anchor_day: u32,
+}
+
+impl Quota {
+    pub fn new(limit: u64, period: CalendarPeriod) -> Self {
+        Quota {
+            limit,
+            period,
+            timezone: Tz::UTC,
+            anchor_day: 1,
+        }
+    }
+
+    pub fn with_timezone(self, timezone: Tz) -> Self {
+        Quota { timezone, ..self }
+    }
+
+    pub fn with_anchor_day(self, anchor_day: u32) -> Self {
+        Quota { anchor_day, ..self }
+    }
+
+    /// Returns the start and the end of the period holding `now`.
+    pub fn period_at(&self, now: SystemTime) -> Result<(SystemTime, SystemTime), RateLimitError> {
+        let now = now
+            .duration_since(time::UNIX_EPOCH)
+            .map_err(|err| RateLimitError::Clock(err.to_string()))?;
+        let (start, end) = self.bounds(now)?;
+
+        Ok((
+            time::UNIX_EPOCH + Duration::from_millis(start),
+            time::UNIX_EPOCH + Duration::from_millis(end),
+        ))
+    }
+
+    /// The start and the end (millis since the Unix epoch) of the period holding `now`.
+    pub(crate) fn bounds(&self, now: Duration) -> Result<(u64, u64), RateLimitError> {
+        validate_quota(self)?;
+        let now = Utc
+            .timestamp_millis_opt(now.as_millis() as i64)
+            .single()
+            .ok_or_else(|| out_of_range(now))?
+            .with_timezone(&self.timezone);
+
+        let (start, end) = match self.period {
+            // the offsets of the timezones change on the hour (or the half hour), so an hour
+            // starts the minutes and seconds of the local time before now
+            CalendarPeriod::Hour => {
+                let into_hour =
+                    chrono::Duration::seconds(i64::from(now.minute() * 60 + now.second()))
+                        + chrono::Duration::nanoseconds(i64::from(now.nanosecond()));
+                let start = now - into_hour;
+                (start, start + chrono::Duration::hours(1))
+            }
+            CalendarPeriod::Day => {
+                let today = now.date_naive();
+                (self.midnight(today)?, self.midnight(add_days(today, 1)?)?)
+            }
+            CalendarPeriod::Week => {
+                let today = now.date_naive();
+                let weekday = today.weekday().number_from_monday();
+                let start = today
+                    .checked_sub_days(Days::new(u64::from((weekday + 7 - self.anchor_day) % 7)))
+                    .ok_or_else(|| out_of_range_date(today))?;
+                (self.midnight(start)?, self.midnight(add_days(start, 7)?)?)
+            }
+            CalendarPeriod::Month => {
+                let today = now.date_naive();
+                let this_month = self.anchor_in(today, 0)?;
+                if today >= this_month {
+                    let next_month = self.anchor_in(today, 1)?;
+                    (self.midnight(this_month)?, self.midnight(next_month)?)
+                } else {
+                    let last_month = self.anchor_in(today, -1)?;
+                    (self.midnight(last_month)?, self.midnight(this_month)?)
+                }
+            }
+        };
+
+        Ok((
+            start.timestamp_millis() as u64,
+            end.timestamp_millis() as u64,
+        ))
+    }
+
+    /// The first instant of the day in the timezone: its midnight, or the first hour after it
+    /// when the clocks skip midnight.
+    fn midnight(&self, date: NaiveDate) -> Result<DateTime<Tz>, RateLimitError> {
+        (0..24)
+            .find_map(|hour| {
+                let local = date.and_hms_opt(hour, 0, 0)?;
+                self.timezone.from_local_datetime(&local).earliest()
+            })
+            .ok_or_else(|| out_of_range_date(date))
+    }
+
+    /// The anchor day of the month `months` after the month of the date, on the last day of the
+    /// month if the month is shorter.
+    fn anchor_in(&self, date: NaiveDate, months: i32) -> Result<NaiveDate, RateLimitError> {
+        let first = date.with_day(1).ok_or_else(|| out_of_range_date(date))?;
+        let first = if months >= 0 {
+            first.checked_add_months(Months::new(months as u32))
+        } else {
+            first.checked_sub_months(Months::new(months.unsigned_abs()))
+        }
+        .ok_or_else(|| out_of_range_date(date))?;
+        let last = first
+            .checked_add_months(Months::new(1))
+            .and_then(|next| next.pred_opt())
+            .ok_or_else(|| out_of_range_date(date))?;
+
+        first
+            .with_day(self.anchor_day.min(last.day()))
+            .ok_or_else(|| out_of_range_date(date))
+    }
+}
+
+/// Calendar quotas, e.g. plan quotas per month, checked with the same client as the per-second
+/// limits. The count of a period is kept for another period after it ends, e.g. to bill it.
+///
+/// The period is computed from the time of the configured time source. With the Redis server
+/// clock the time is read by one more round trip before the script runs.
+impl<C: ConnectionLike> RateLimiterRedis<C> {
+    pub fn record_quota(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        quota: &Quota,
+    ) -> Result<Decision, RateLimitError> {
+        self.quota(key_prefix, resource, subject, quota, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterRedis::record_quota`], for a request which costs `cost` units, see
+    /// [`RateLimiterRedis::record_fixed_window_weighted`].
+    pub fn record_quota_weighted(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        quota: &Quota,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.quota(key_prefix, resource, subject, quota, cost, Mode::Record)
+    }
+
+    pub fn fetch_quota(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        quota: &Quota,
+    ) -> Result<Decision, RateLimitError> {
+        self.quota(key_prefix, resource, subject, quota, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedis::peek_fixed_window`], for
+    /// [`RateLimiterRedis::record_quota_weighted`].
+    pub fn peek_quota(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        quota: &Quota,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.quota(key_prefix, resource, subject, quota, cost, Mode::Peek)
+    }
+
+    fn quota(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        quota: &Quota,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now(&key)?;
+
+        self.scripts
+            .quota(&key, now, quota, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)
+    }
+
+    /// Clears the count of the current period of the quota.
+    pub fn reset_quota(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        quota: &Quota,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now(&key)?;
+        let (start, _) = quota.bounds(now)?;
+
+        self.conn.del::<_, ()>(quota_key(&key, quota, start))?;
+
+        Ok(())
+    }
+}
+
+/// The count of the period of the quota starting at `start` (millis since the Unix epoch).
+pub(crate) fn quota_key(key: &str, quota: &Quota, start: u64) -> String {
+    format!("{key}:{}:{start}", quota.period)
+}
+
+/// Checks the quota can be enforced.
+fn validate_quota(quota: &Quota) -> Result<(), RateLimitError> {
+    if quota.limit == 0 {
+        return Err(RateLimitError::InvalidConfig(
+            "the limit must be at least one".to_string(),
+        ));
+    }
+    let days = match quota.period {
+        CalendarPeriod::Hour | CalendarPeriod::Day => return Ok(()),
+        CalendarPeriod::Week => 7,
+        CalendarPeriod::Month => 31,
+    };
+    if !(1..=days).contains(&quota.anchor_day) {
+        return Err(RateLimitError::InvalidConfig(format!(
+            "the anchor day of a {} must be from 1 to {days}, got {}",
+            quota.period, quota.anchor_day
+        )));
+    }
+
+    Ok(())
+}
+
+fn add_days(date: NaiveDate, days: u64) -> Result<NaiveDate, RateLimitError> {
+    date.checked_add_days(Days::new(days))
+        .ok_or_else(|| out_of_range_date(date))
+}
+
+fn out_of_range(now: Duration) -> RateLimitError {
+    RateLimitError::Clock(format!(
+        "the time {now:?} is out of the range of the calendar"
+    ))
+}
+
+fn out_of_range_date(date: NaiveDate) -> RateLimitError {
+    RateLimitError::Clock(format!(
+        "the date {date} is out of the range of the calendar"
+    ))
+}
@@ -0,0 +1,206 @@
+use crate::backend::Backend;
+use crate::decision::Decision;
+use crate::error::RateLimitError;
+use crate::rate_limiter_redis::RateLimiterRedis;
+use crate::rule::Rule;
+use std::fmt;
+use std::str::FromStr;
+
+/// A rate limiter which does not care which algorithm is behind it, so the algorithm can be
+/// swapped (e.g. from configuration by [`Algorithm`]) without touching the call sites.
+pub trait RateLimiter {
+    /// Checks a request of the subject on the resource, and records it if allowed.
+    fn check(&mut self, resource: &str, subject: &str) -> Result<Decision, RateLimitError> {
+        self.check_weighted(resource, subject, 1)
+    }
+
+    /// Checks a request which costs `cost` units, and records all of them if allowed.
+    fn check_weighted(
+        &mut self,
+        resource: &str,
+        subject: &str,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError>;
+
+    /// Returns the current state of the limit of the subject on the resource, i.e. `remaining`
+    /// before a request, without recording anything.
+    fn fetch(&mut self, resource: &str, subject: &str) -> Result<Decision, RateLimitError>;
+
+    /// Returns exactly the decision [`RateLimiter::check`] would make right now, without
+    /// recording anything.
+    fn peek(&mut self, resource: &str, subject: &str) -> Result<Decision, RateLimitError> {
+        self.peek_weighted(resource, subject, 1)
+    }
+
+    /// Returns exactly the decision [`RateLimiter::check_weighted`] would make right now,
+    /// without recording anything. Unlike [`RateLimiter::fetch`], `remaining` is what would be
+    /// left after the request.
+    fn peek_weighted(
+        &mut self,
+        resource: &str,
+        subject: &str,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError>;
+
+    /// Clears what has been recorded for the subject on the resource.
+    fn reset(&mut self, resource: &str, subject: &str) -> Result<(), RateLimitError>;
+}
+
+/// Defines a [`RateLimiter`] running one algorithm on a [`Backend`].
+macro_rules! algorithm {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        pub struct $name<B = RateLimiterRedis> {
+            client: B,
+            key_prefix: String,
+            rule: Rule,
+        }
+
+        impl<B: Backend> $name<B> {
+            pub fn new(client: B, key_prefix: &str, rule: Rule) -> Self {
+                $name {
+                    client,
+                    key_prefix: key_prefix.to_string(),
+                    rule,
+                }
+            }
+        }
+
+        impl<B: Backend> RateLimiter for $name<B> {
+            fn check_weighted(
+                &mut self,
+                resource: &str,
+                subject: &str,
+                cost: u64,
+            ) -> Result<Decision, RateLimitError> {
+                self.client.record_weighted(
+                    Algorithm::$name,
+                    &self.key_prefix,
+                    resource,
+                    subject,
+                    &self.rule,
+                    cost,
+                )
+            }
+
+            fn fetch(&mut self, resource: &str, subject: &str) -> Result<Decision, RateLimitError> {
+                self.client
+                    .fetch(Algorithm::$name, &self.key_prefix, resource, subject, &self.rule)
+            }
+
+            fn peek_weighted(
+                &mut self,
+                resource: &str,
+                subject: &str,
+                cost: u64,
+            ) -> Result<Decision, RateLimitError> {
+                self.client.peek(
+                    Algorithm::$name,
+                    &self.key_prefix,
+                    resource,
+                    subject,
+                    &self.rule,
+                    cost,
+                )
+            }
+
+            fn reset(&mut self, resource: &str, subject: &str) -> Result<(), RateLimitError> {
+                self.client
+                    .reset(Algorithm::$name, &self.key_prefix, resource, subject, &self.rule)
+            }
+        }
+    };
+}
+
+algorithm!(
+    /// Fixed window algorithm, see [`RateLimiterRedis::record_fixed_window`].
+    FixedWindow
+);
+
+algorithm!(
+    /// Sliding log algorithm, see [`RateLimiterRedis::record_sliding_log`].
+    SlidingLog
+);
+
+algorithm!(
+    /// Sliding window algorithm, see [`RateLimiterRedis::record_sliding_window`].
+    SlidingWindow
+);
+
+algorithm!(
+    /// Leaky bucket algorithm, see [`RateLimiterRedis::record_leaky_bucket`].
+    LeakyBucket
+);
+
+algorithm!(
+    /// Token bucket algorithm, see [`RateLimiterRedis::record_token_bucket`].
+    TokenBucket
+);
+
+algorithm!(
+    /// GCRA (generic cell rate algorithm), see [`RateLimiterRedis::record_gcra`].
+    Gcra
+);
+
+/// The algorithms which can be chosen from configuration, e.g. `"sliding_window".parse()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    FixedWindow,
+    SlidingLog,
+    SlidingWindow,
+    LeakyBucket,
+    TokenBucket,
+    Gcra,
+}
+
+impl Algorithm {
+    /// Builds the rate limiter of this algorithm on top of the backend.
+    pub fn build<B: Backend + 'static>(
+        self,
+        client: B,
+        key_prefix: &str,
+        rule: Rule,
+    ) -> Box<dyn RateLimiter> {
+        match self {
+            Algorithm::FixedWindow => Box::new(FixedWindow::new(client, key_prefix, rule)),
+            Algorithm::SlidingLog => Box::new(SlidingLog::new(client, key_prefix, rule)),
+            Algorithm::SlidingWindow => Box::new(SlidingWindow::new(client, key_prefix, rule)),
+            Algorithm::LeakyBucket => Box::new(LeakyBucket::new(client, key_prefix, rule)),
+            Algorithm::TokenBucket => Box::new(TokenBucket::new(client, key_prefix, rule)),
+            Algorithm::Gcra => Box::new(Gcra::new(client, key_prefix, rule)),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = RateLimitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fixed_window" => Ok(Algorithm::FixedWindow),
+            "sliding_log" => Ok(Algorithm::SlidingLog),
+            "sliding_window" => Ok(Algorithm::SlidingWindow),
+            "leaky_bucket" => Ok(Algorithm::LeakyBucket),
+            "token_bucket" => Ok(Algorithm::TokenBucket),
+            "gcra" => Ok(Algorithm::Gcra),
+            _ => Err(RateLimitError::InvalidConfig(format!(
+                "unknown rate limiting algorithm: {s}"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Algorithm::FixedWindow => "fixed_window",
+            Algorithm::SlidingLog => "sliding_log",
+            Algorithm::SlidingWindow => "sliding_window",
+            Algorithm::LeakyBucket => "leaky_bucket",
+            Algorithm::TokenBucket => "token_bucket",
+            Algorithm::Gcra => "gcra",
+        };
+
+        write!(f, "{name}")
+    }
+}
@@ -0,0 +1,1115 @@
+use crate::backend::Backend;
+use crate::clock::{Clock, SystemClock};
+use crate::decision::{Decision, Schedule};
+use crate::error::RateLimitError;
+use crate::rate_limiter::Algorithm;
+use crate::rate_limiter_redis::{
+    gcra_key, subject_key, token_bucket_keys, validate_cost, validate_rule, window_key, Mode,
+};
+use crate::rule::Rule;
+use redis::{ErrorKind, RedisError};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{self, Duration};
+
+/// The number of shards of [`RateLimiterMemory::new`].
+const DEFAULT_SHARDS: usize = 16;
+
+/// The number of writes to a shard between two sweeps of its expired keys.
+const SWEEP_EVERY: u64 = 1024;
+
+/// Tolerance for the rounding of the fractional algorithms, the same as in the scripts.
+const EPSILON: f64 = 1e-9;
+
+/// The rate limiter on the memory of this process, for tests or for tools running without a
+/// Redis. It runs the same algorithms as the scripts of
+/// [`RateLimiterRedis`](crate::rate_limiter_redis::RateLimiterRedis) with the clock of this
+/// host (or the `clock` given), so it makes the same decisions.
+///
+/// The keys are spread over shards, each behind its own lock, and all keys of a subject live in
+/// the same shard so every check stays atomic. Clones share the same state.
+#[derive(Clone)]
+pub struct RateLimiterMemory {
+    /// The clock the algorithms read, e.g. a [`MockClock`](crate::clock::MockClock) in tests.
+    pub clock: Arc<dyn Clock>,
+    shards: Arc<[Mutex<Shard>]>,
+}
+
+/// What the algorithms keep under a key, in place of the Redis types used by the scripts.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    /// The requests of a fixed window.
+    Count(u64),
+    /// The sorted times of the logged requests.
+    Log(Vec<u64>),
+    /// The tokens of a token bucket and when they were last refilled.
+    Tokens { tokens: f64, last_refill: u64 },
+    /// The level of a leaky bucket and when it last leaked.
+    Level { level: f64, last_leak: u64 },
+    /// The theoretical arrival time of GCRA.
+    Tat(f64),
+}
+
+struct Entry {
+    value: Value,
+    /// In millis since the Unix epoch. The key is gone once this time has passed, like a key
+    /// given a TTL by PEXPIRE.
+    expires_at: u64,
+}
+
+#[derive(Default)]
+struct Shard {
+    entries: HashMap<String, Entry>,
+    writes: u64,
+}
+
+impl Shard {
+    /// Returns the value of the key, dropping it first if it has expired.
+    fn get(&mut self, key: &str, now: u64) -> Option<&mut Value> {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.expires_at < now)
+        {
+            self.entries.remove(key);
+        }
+
+        self.entries.get_mut(key).map(|entry| &mut entry.value)
+    }
+
+    /// Sets the value of the key for `ttl` millis, or removes the key if `ttl` is zero.
+    fn set(&mut self, key: String, value: Value, now: u64, ttl: u64) {
+        if ttl == 0 {
+            self.entries.remove(&key);
+            return;
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                expires_at: now + ttl,
+            },
+        );
+
+        self.writes += 1;
+        if self.writes.is_multiple_of(SWEEP_EVERY) {
+            self.entries.retain(|_, entry| entry.expires_at >= now);
+        }
+    }
+
+    fn remove(&mut self, keys: &[String]) {
+        for key in keys {
+            self.entries.remove(key);
+        }
+    }
+
+    fn count(&mut self, key: &str, now: u64) -> Result<u64, RateLimitError> {
+        match self.get(key, now) {
+            None => Ok(0),
+            Some(Value::Count(count)) => Ok(*count),
+            Some(_) => Err(wrong_type(key)),
+        }
+    }
+}
+
+impl Default for RateLimiterMemory {
+    fn default() -> Self {
+        RateLimiterMemory::new()
+    }
+}
+
+impl RateLimiterMemory {
+    pub fn new() -> Self {
+        RateLimiterMemory::with_shards(DEFAULT_SHARDS)
+    }
+
+    /// Spreads the keys over `shards` locks (at least one), so more threads can check
+    /// different subjects at the same time.
+    pub fn with_shards(shards: usize) -> Self {
+        RateLimiterMemory {
+            clock: Arc::new(SystemClock),
+            shards: (0..shards.max(1))
+                .map(|_| Mutex::new(Shard::default()))
+                .collect(),
+        }
+    }
+
+    /// Locks the shard holding the keys of the subject. A shard poisoned by a panic is still
+    /// used, since every check leaves its keys in a state the algorithms can read.
+    fn shard(&self, key: &str) -> MutexGuard<'_, Shard> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let shard = &self.shards[hasher.finish() as usize % self.shards.len()];
+
+        shard.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the current time in millis since the Unix epoch from the clock.
+    fn now_millis(&self) -> Result<u64, RateLimitError> {
+        Ok(self.clock.now()?.as_millis() as u64)
+    }
+
+    pub fn record_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Checks a request which costs `cost` units (e.g. the rows of a bulk export or the tokens
+    /// of an LLM call) instead of one, and records all of them if at least `cost` units remain.
+    /// The decision tells the units which remain. A cost of zero or above the limit (the burst
+    /// for the buckets and GCRA) is an [`RateLimitError::InvalidConfig`].
+    pub fn record_fixed_window_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Returns exactly the decision [`RateLimiterMemory::record_fixed_window_weighted`] would make
+    /// for a request of `cost` units right now, without recording it or changing any key. Unlike
+    /// [`RateLimiterMemory::fetch_fixed_window`], `remaining` is what would be left after the request.
+    pub fn peek_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    /// See `scripts/fixed_window.lua`.
+    fn fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        validate_rule(rule)?;
+        validate_cost(cost, rule.limit)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now_millis()?;
+        let size = rule.period_millis();
+        let limit = rule.limit;
+
+        let window = now / size * size;
+        let window_key = window_key(&key, rule, Duration::from_millis(now), 0);
+        let reset_at = window + size;
+
+        let mut shard = self.shard(&key);
+        let mut count = shard.count(&window_key, now)?;
+        if count + cost > limit {
+            let remaining = limit.saturating_sub(count);
+            return Ok(decision(false, limit, remaining, reset_at, reset_at - now));
+        }
+
+        if mode != Mode::Fetch {
+            count += cost;
+        }
+        if mode == Mode::Record {
+            shard.set(window_key, Value::Count(count), now, size);
+        }
+
+        Ok(decision(true, limit, limit - count, reset_at, 0))
+    }
+
+    pub fn reset_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.clock.now()?;
+
+        self.shard(&key).remove(&[window_key(&key, rule, now, 0)]);
+
+        Ok(())
+    }
+
+    pub fn record_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterMemory::record_sliding_log`], for a request which costs `cost`
+    /// units, see [`RateLimiterMemory::record_fixed_window_weighted`].
+    pub fn record_sliding_log_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterMemory::peek_fixed_window`], for
+    /// [`RateLimiterMemory::record_sliding_log_weighted`].
+    pub fn peek_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    /// See `scripts/sliding_log.lua`.
+    fn sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        validate_rule(rule)?;
+        validate_cost(cost, rule.limit)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now_millis()?;
+        let size = rule.period_millis();
+        let limit = rule.limit;
+
+        // requests logged at or before this time have left the window
+        let expired = now.saturating_sub(size);
+
+        let mut shard = self.shard(&key);
+        let mut log = match shard.get(&key, now) {
+            None => Vec::new(),
+            Some(Value::Log(log)) => std::mem::take(log),
+            Some(_) => return Err(wrong_type(&key)),
+        };
+        let mut first = log.partition_point(|&time| time <= expired);
+        if mode == Mode::Record {
+            log.drain(..first);
+            first = 0;
+        }
+        let in_window = &log[first..];
+        let count = in_window.len() as u64;
+        let newest = log.last().copied();
+
+        let actual = if count + cost > limit {
+            // a request is allowed again once enough of the oldest requests have left the window
+            let oldest = in_window[(count + cost - limit - 1) as usize];
+            let reset_at = newest.map_or(now, |newest| newest + size);
+            let remaining = limit.saturating_sub(count);
+            decision(false, limit, remaining, reset_at, oldest + size - now)
+        } else if mode == Mode::Fetch {
+            let reset_at = newest
+                .filter(|_| count > 0)
+                .map_or(now, |newest| newest + size);
+            decision(true, limit, limit - count, reset_at, 0)
+        } else {
+            if mode == Mode::Record {
+                let at = log.partition_point(|&time| time <= now);
+                log.splice(at..at, std::iter::repeat_n(now, cost as usize));
+            }
+            decision(true, limit, limit - count - cost, now + size, 0)
+        };
+
+        if mode == Mode::Record && actual.allowed {
+            shard.set(key, Value::Log(log), now, size);
+        } else if let Some(Value::Log(stored)) = shard.get(&key, now) {
+            *stored = log;
+        }
+
+        Ok(actual)
+    }
+
+    pub fn reset_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.shard(&key).remove(std::slice::from_ref(&key));
+
+        Ok(())
+    }
+
+    pub fn record_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterMemory::record_sliding_window`], for a request which costs `cost`
+    /// units, see [`RateLimiterMemory::record_fixed_window_weighted`].
+    pub fn record_sliding_window_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterMemory::peek_fixed_window`], for
+    /// [`RateLimiterMemory::record_sliding_window_weighted`].
+    pub fn peek_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    /// See `scripts/sliding_window.lua`.
+    fn sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        validate_rule(rule)?;
+        validate_cost(cost, rule.limit)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now_millis()?;
+        let size = rule.period_millis();
+        let limit = rule.limit;
+
+        let current_window = now / size * size;
+        let current_key = window_key(&key, rule, Duration::from_millis(now), 0);
+        let previous_key = window_key(&key, rule, Duration::from_millis(now), 1);
+        let window_end = current_window + size;
+
+        let mut shard = self.shard(&key);
+        let weight = (window_end - now) as f64 / size as f64;
+        let previous_count = shard.count(&previous_key, now)?;
+        let mut current_count = shard.count(&current_key, now)?;
+        let mut count = current_count + (previous_count as f64 * weight + 0.5).floor() as u64;
+
+        // the requests of the previous window are weighted out at the end of the current window,
+        // and the requests of the current window at the end of the next one
+        let reset_at = |current_count: u64| {
+            if current_count > 0 {
+                window_end + size
+            } else if previous_count > 0 {
+                window_end
+            } else {
+                now
+            }
+        };
+
+        if count + cost > limit {
+            let target = limit as f64 - cost as f64 + 0.5;
+            let (current, previous) = (current_count as f64, previous_count as f64);
+            let retry_at = if current < target {
+                let w = (target - current) / previous;
+                window_end as f64 - w * size as f64
+            } else {
+                let w = target / current;
+                (window_end + size) as f64 - w * size as f64
+            };
+            let retry_after = ((retry_at - now as f64).floor() + 1.0).max(0.0);
+            return Ok(decision(
+                false,
+                limit,
+                limit.saturating_sub(count),
+                reset_at(current_count),
+                retry_after as u64,
+            ));
+        }
+
+        if mode != Mode::Fetch {
+            current_count += cost;
+            count += cost;
+        }
+        if mode == Mode::Record {
+            shard.set(current_key, Value::Count(current_count), now, size * 2);
+        }
+
+        Ok(decision(
+            true,
+            limit,
+            limit - count,
+            reset_at(current_count),
+            0,
+        ))
+    }
+
+    pub fn reset_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.clock.now()?;
+
+        self.shard(&key).remove(&[
+            window_key(&key, rule, now, 0),
+            window_key(&key, rule, now, 1),
+        ]);
+
+        Ok(())
+    }
+
+    pub fn record_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterMemory::record_leaky_bucket`], for a request which costs `cost`
+    /// units, see [`RateLimiterMemory::record_fixed_window_weighted`].
+    pub fn record_leaky_bucket_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterMemory::peek_fixed_window`], for
+    /// [`RateLimiterMemory::record_leaky_bucket_weighted`].
+    pub fn peek_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    /// See `scripts/leaky_bucket.lua`.
+    fn leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        validate_rule(rule)?;
+        validate_cost(cost, rule.burst())?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now_millis()?;
+        let capacity = rule.burst() as f64;
+
+        let mut shard = self.shard(&key);
+        let mut bucket = LeakyBucket::load(&mut shard, &key, now, rule)?;
+
+        // the room left in the bucket, less than the cost when the request is denied
+        let remaining = |level: f64| (capacity - level + EPSILON).floor() as u64;
+
+        if bucket.level + cost as f64 > capacity + EPSILON {
+            return Ok(decision(
+                false,
+                rule.burst(),
+                remaining(bucket.level).min(cost - 1),
+                now + bucket.time_until(0.0),
+                bucket.time_until(capacity - cost as f64),
+            ));
+        }
+
+        if mode != Mode::Fetch {
+            bucket.level += cost as f64;
+        }
+        if mode == Mode::Record {
+            bucket.store(&mut shard, key, now);
+        }
+
+        Ok(decision(
+            true,
+            rule.burst(),
+            remaining(bucket.level),
+            now + bucket.time_until(0.0),
+            0,
+        ))
+    }
+
+    /// Shapes the traffic instead of rejecting it, see
+    /// [`RateLimiterRedis::schedule_leaky_bucket`](crate::rate_limiter_redis::RateLimiterRedis::schedule_leaky_bucket)
+    /// and `scripts/leaky_bucket_shape.lua`.
+    pub fn schedule_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<Schedule, RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now_millis()?;
+        let capacity = rule.burst() as f64;
+        let max_delay = max_delay.as_millis() as u64;
+
+        let mut shard = self.shard(&key);
+        let mut bucket = LeakyBucket::load(&mut shard, &key, now, rule)?;
+
+        // the request is queued above the capacity, and may be processed once there is room for it
+        let delay = bucket.time_until(capacity - 1.0);
+        if delay > max_delay {
+            return Ok(schedule(false, now + delay - max_delay, delay - max_delay));
+        }
+
+        bucket.level += 1.0;
+        bucket.store(&mut shard, key, now);
+
+        Ok(schedule(true, now + delay, delay))
+    }
+
+    /// Schedules the request like [`RateLimiterMemory::schedule_leaky_bucket`], and blocks
+    /// until it may be processed if it was scheduled.
+    pub fn wait_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<Schedule, RateLimitError> {
+        let schedule =
+            self.schedule_leaky_bucket(key_prefix, resource, subject, rule, max_delay)?;
+        if schedule.scheduled {
+            std::thread::sleep(schedule.delay);
+        }
+
+        Ok(schedule)
+    }
+
+    pub fn reset_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.shard(&key).remove(std::slice::from_ref(&key));
+
+        Ok(())
+    }
+
+    pub fn record_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterMemory::record_token_bucket`], for a request which costs `cost`
+    /// units, see [`RateLimiterMemory::record_fixed_window_weighted`].
+    pub fn record_token_bucket_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterMemory::peek_fixed_window`], for
+    /// [`RateLimiterMemory::record_token_bucket_weighted`].
+    pub fn peek_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    /// See `scripts/token_bucket.lua`.
+    fn token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        validate_rule(rule)?;
+        validate_cost(cost, rule.burst())?;
+        let key = subject_key(key_prefix, resource, subject);
+        let [_, tokens_key] = token_bucket_keys(&key);
+        let now = self.now_millis()?;
+        let period = rule.period_millis() as f64;
+        let capacity = rule.burst() as f64;
+        let refill = rule.limit as f64;
+
+        // the tokens accrue continuously since the last refill, up to the capacity
+        let mut shard = self.shard(&key);
+        let mut tokens = match shard.get(&tokens_key, now) {
+            None => capacity,
+            Some(Value::Tokens {
+                tokens,
+                last_refill,
+            }) => capacity.min(*tokens + now.saturating_sub(*last_refill) as f64 * refill / period),
+            Some(_) => return Err(wrong_type(&tokens_key)),
+        };
+
+        // the time (millis from now) until the bucket holds the given tokens
+        let time_until = |tokens: f64, target: f64| {
+            if tokens + EPSILON >= target {
+                0
+            } else {
+                ((target - tokens) * period / refill - EPSILON).ceil() as u64
+            }
+        };
+
+        if tokens + EPSILON < cost as f64 {
+            return Ok(decision(
+                false,
+                rule.burst(),
+                ((tokens + EPSILON).floor() as u64).min(cost - 1),
+                now + time_until(tokens, capacity),
+                time_until(tokens, cost as f64),
+            ));
+        }
+
+        if mode != Mode::Fetch {
+            tokens -= cost as f64;
+        }
+        if mode == Mode::Record {
+            // a bucket left alone until it is full again is the same as a missing one
+            let ttl = time_until(tokens, capacity);
+            let value = Value::Tokens {
+                tokens: lua_tostring(tokens),
+                last_refill: now,
+            };
+            shard.set(tokens_key, value, now, ttl);
+        }
+
+        Ok(decision(
+            true,
+            rule.burst(),
+            (tokens + EPSILON).floor() as u64,
+            now + time_until(tokens, capacity),
+            0,
+        ))
+    }
+
+    pub fn reset_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.shard(&key).remove(&token_bucket_keys(&key));
+
+        Ok(())
+    }
+
+    /// GCRA, see [`RateLimiterRedis::record_gcra`](crate::rate_limiter_redis::RateLimiterRedis::record_gcra).
+    pub fn record_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterMemory::record_gcra`], for a request which costs `cost`
+    /// units, see [`RateLimiterMemory::record_fixed_window_weighted`].
+    pub fn record_gcra_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterMemory::peek_fixed_window`], for
+    /// [`RateLimiterMemory::record_gcra_weighted`].
+    pub fn peek_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    /// See `scripts/gcra.lua`.
+    fn gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        validate_rule(rule)?;
+        validate_cost(cost, rule.burst())?;
+        let key = subject_key(key_prefix, resource, subject);
+        let tat_key = gcra_key(&key);
+        let now = self.now_millis()?;
+        let burst = rule.burst();
+
+        // every request pushes the theoretical arrival time (TAT) one interval further per unit of
+        // its cost, and is allowed as long as the TAT it would set is at most `burst` intervals
+        // ahead of now
+        let interval = rule.period_millis() as f64 / rule.limit as f64;
+        let tolerance = interval * burst as f64;
+
+        let mut shard = self.shard(&key);
+        let mut tat = match shard.get(&tat_key, now) {
+            None => now as f64,
+            Some(Value::Tat(tat)) => tat.max(now as f64),
+            Some(_) => return Err(wrong_type(&tat_key)),
+        };
+        let new_tat = tat + interval * cost as f64;
+        let allow_at = new_tat - tolerance;
+
+        // the intervals left before the TAT is `burst` intervals ahead of now
+        let remaining = |tat: f64| {
+            let remaining = ((now as f64 + tolerance - tat) / interval + EPSILON).floor();
+            remaining.max(0.0) as u64
+        };
+
+        if allow_at > now as f64 + EPSILON {
+            return Ok(decision(
+                false,
+                burst,
+                remaining(tat).min(cost - 1),
+                tat.ceil() as u64,
+                (allow_at - now as f64 - EPSILON).ceil() as u64,
+            ));
+        }
+
+        if mode != Mode::Fetch {
+            tat = new_tat;
+        }
+        if mode == Mode::Record {
+            let ttl = (tat - now as f64).ceil() as u64;
+            shard.set(tat_key, Value::Tat(round_millis(tat)), now, ttl);
+        }
+
+        Ok(decision(true, burst, remaining(tat), tat.ceil() as u64, 0))
+    }
+
+    pub fn reset_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.shard(&key).remove(&[gcra_key(&key)]);
+
+        Ok(())
+    }
+}
+
+/// Runs the algorithms on the memory of this process, so the limits are only shared by the
+/// clones of the limiter.
+impl Backend for RateLimiterMemory {
+    fn record_weighted(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        match algorithm {
+            Algorithm::FixedWindow => {
+                self.record_fixed_window_weighted(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::SlidingLog => {
+                self.record_sliding_log_weighted(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::SlidingWindow => {
+                self.record_sliding_window_weighted(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::LeakyBucket => {
+                self.record_leaky_bucket_weighted(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::TokenBucket => {
+                self.record_token_bucket_weighted(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::Gcra => self.record_gcra_weighted(key_prefix, resource, subject, rule, cost),
+        }
+    }
+
+    fn fetch(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        match algorithm {
+            Algorithm::FixedWindow => self.fetch_fixed_window(key_prefix, resource, subject, rule),
+            Algorithm::SlidingLog => self.fetch_sliding_log(key_prefix, resource, subject, rule),
+            Algorithm::SlidingWindow => {
+                self.fetch_sliding_window(key_prefix, resource, subject, rule)
+            }
+            Algorithm::LeakyBucket => self.fetch_leaky_bucket(key_prefix, resource, subject, rule),
+            Algorithm::TokenBucket => self.fetch_token_bucket(key_prefix, resource, subject, rule),
+            Algorithm::Gcra => self.fetch_gcra(key_prefix, resource, subject, rule),
+        }
+    }
+
+    fn peek(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        match algorithm {
+            Algorithm::FixedWindow => {
+                self.peek_fixed_window(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::SlidingLog => {
+                self.peek_sliding_log(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::SlidingWindow => {
+                self.peek_sliding_window(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::LeakyBucket => {
+                self.peek_leaky_bucket(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::TokenBucket => {
+                self.peek_token_bucket(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::Gcra => self.peek_gcra(key_prefix, resource, subject, rule, cost),
+        }
+    }
+
+    fn reset(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        match algorithm {
+            Algorithm::FixedWindow => self.reset_fixed_window(key_prefix, resource, subject, rule),
+            Algorithm::SlidingLog => self.reset_sliding_log(key_prefix, resource, subject),
+            Algorithm::SlidingWindow => {
+                self.reset_sliding_window(key_prefix, resource, subject, rule)
+            }
+            Algorithm::LeakyBucket => self.reset_leaky_bucket(key_prefix, resource, subject),
+            Algorithm::TokenBucket => self.reset_token_bucket(key_prefix, resource, subject),
+            Algorithm::Gcra => self.reset_gcra(key_prefix, resource, subject),
+        }
+    }
+}
+
+/// The level of a leaky bucket, leaked continuously since the last call down to empty.
+struct LeakyBucket {
+    level: f64,
+    period: f64,
+    leak: f64,
+}
+
+impl LeakyBucket {
+    fn load(shard: &mut Shard, key: &str, now: u64, rule: &Rule) -> Result<Self, RateLimitError> {
+        let period = rule.period_millis() as f64;
+        let leak = rule.limit as f64;
+        let (level, last_leak) = match shard.get(key, now) {
+            None => (0.0, now),
+            Some(Value::Level { level, last_leak }) => (*level, *last_leak),
+            Some(_) => return Err(wrong_type(key)),
+        };
+        let level = (level - now.saturating_sub(last_leak) as f64 * leak / period).max(0.0);
+
+        Ok(LeakyBucket {
+            level,
+            period,
+            leak,
+        })
+    }
+
+    /// The time (millis from now) until the bucket has leaked down to the given level.
+    fn time_until(&self, target: f64) -> u64 {
+        if self.level <= target + EPSILON {
+            return 0;
+        }
+
+        ((self.level - target) * self.period / self.leak - EPSILON).ceil() as u64
+    }
+
+    fn store(&self, shard: &mut Shard, key: String, now: u64) {
+        let value = Value::Level {
+            level: lua_tostring(self.level),
+            last_leak: now,
+        };
+        shard.set(key, value, now, self.time_until(0.0));
+    }
+}
+
+/// The number as stored by `tostring` in the scripts, i.e. with 14 significant digits.
+fn lua_tostring(number: f64) -> f64 {
+    format!("{number:.13e}").parse().unwrap_or(number)
+}
+
+/// The number as stored by `string.format('%.3f')` in the scripts.
+fn round_millis(number: f64) -> f64 {
+    format!("{number:.3}").parse().unwrap_or(number)
+}
+
+fn decision(
+    allowed: bool,
+    limit: u64,
+    remaining: u64,
+    reset_at: u64,
+    retry_after: u64,
+) -> Decision {
+    Decision {
+        allowed,
+        limit,
+        remaining,
+        reset_at: time::UNIX_EPOCH + Duration::from_millis(reset_at),
+        retry_after: Duration::from_millis(retry_after),
+    }
+}
+
+fn schedule(scheduled: bool, process_at: u64, delay: u64) -> Schedule {
+    Schedule {
+        scheduled,
+        process_at: time::UNIX_EPOCH + Duration::from_millis(process_at),
+        delay: Duration::from_millis(delay),
+    }
+}
+
+/// The error Redis replies when a key holds the value of another algorithm.
+fn wrong_type(key: &str) -> RateLimitError {
+    RateLimitError::WrongType(RedisError::from((
+        ErrorKind::TypeError,
+        "WRONGTYPE Operation against a key holding the wrong kind of value",
+        key.to_string(),
+    )))
+}
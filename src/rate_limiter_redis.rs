@@ -1,91 +1,731 @@
-use redis::{Commands, Connection};
-use std::time::{self, Duration, SystemTime};
+use crate::backend::Backend;
+use crate::clock::{Clock, SystemClock};
+use crate::decision::{Decision, Schedule};
+use crate::error::RateLimitError;
+#[cfg(feature = "calendar")]
+use crate::quota::{quota_key, Quota};
+use crate::rate_limiter::Algorithm;
+use crate::rule::{MultiWindowRule, Rule};
+#[cfg(feature = "sentinel")]
+use crate::sentinel::{SentinelConfig, SentinelConnection};
+use redis::{Commands, Connection, ConnectionLike, FromRedisValue, Script, ScriptInvocation};
+use std::sync::Arc;
+use std::time::Duration;
 
-pub struct RateLimiterRedis {
-    pub conn: Connection,
-    pub limit_per_sec: u64,
+/// The rate limiter on a connection to a single Redis, to a Redis Cluster (see
+/// [`RateLimiterRedisCluster`]) or to a Redis monitored by Sentinel (see
+/// [`RateLimiterRedisSentinel`]).
+pub struct RateLimiterRedis<C = Connection> {
+    pub conn: C,
+    /// The connection the read-only `fetch_*` and `peek_*` calls are sent to instead of `conn`,
+    /// if any.
+    pub replica: Option<C>,
+    pub time_source: TimeSource,
+    /// The clock read when `time_source` is [`TimeSource::Client`], e.g. a
+    /// [`MockClock`](crate::clock::MockClock) in tests.
+    pub clock: Arc<dyn Clock>,
+    pub(crate) scripts: Scripts,
+}
+
+/// The clock which "now" is taken from when computing windows and refills.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeSource {
+    /// The Redis server clock (the TIME command), shared by all clients of the same Redis.
+    #[default]
+    Server,
+    /// The clock of the calling host (see [`Clock`]), which may be skewed between hosts.
+    Client,
+}
+
+impl TimeSource {
+    /// Returns the first argument of the scripts: the current time in millis when the client
+    /// clock is used, or an empty string to let the script read the Redis server clock.
+    pub(crate) fn script_arg(self, clock: &dyn Clock) -> Result<String, RateLimitError> {
+        match self {
+            TimeSource::Server => Ok(String::new()),
+            TimeSource::Client => Ok(clock.now()?.as_millis().to_string()),
+        }
+    }
+}
+
+/// What a script does with the request it checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    /// Records the request if it is allowed.
+    Record,
+    /// Records the request like [`Mode::Record`], and returns the receipt to refund it by.
+    Refundable,
+    /// Returns the decision recording the request would get, without recording it.
+    Peek,
+    /// Returns the current state of the limit, i.e. `remaining` before the request.
+    Fetch,
+}
+
+impl Mode {
+    /// Returns the fourth argument of the scripts.
+    pub(crate) fn script_arg(self) -> &'static str {
+        match self {
+            Mode::Record => "record",
+            Mode::Refundable => "refundable",
+            Mode::Peek => "peek",
+            Mode::Fetch => "fetch",
+        }
+    }
+}
+
+/// Builds the script in `src/scripts/{name}.lua` with the shared clock and receipt helpers
+/// prepended.
+macro_rules! script {
+    ($name:literal) => {
+        Script::new(concat!(
+            include_str!("scripts/clock.lua"),
+            include_str!("scripts/receipt.lua"),
+            include_str!(concat!("scripts/", $name, ".lua"))
+        ))
+    };
+}
+
+/// Builds the script checking a request against several limits at once, with the script of
+/// every algorithm prepended as a function of its keys and arguments.
+macro_rules! limits_script {
+    ($($name:literal),*) => {
+        Script::new(concat!(
+            include_str!("scripts/clock.lua"),
+            include_str!("scripts/receipt.lua"),
+            $(
+                "local function ",
+                $name,
+                "(KEYS, ARGV)\n",
+                include_str!(concat!("scripts/", $name, ".lua")),
+                "end\n",
+            )*
+            include_str!("scripts/limits.lua")
+        ))
+    };
+}
+
+/// Server-side scripts which check a request, and record it if allowed, in one atomic step.
+pub(crate) struct Scripts {
+    fixed_window: Script,
+    sliding_log: Script,
+    sliding_window: Script,
+    leaky_bucket: Script,
+    leaky_bucket_shape: Script,
+    token_bucket: Script,
+    gcra: Script,
+    refund: Script,
+    lease_acquire: Script,
+    lease_release: Script,
+    lease_renew: Script,
+    limits: Script,
+    #[cfg(feature = "calendar")]
+    quota: Script,
+    time: Script,
+}
+
+impl Scripts {
+    pub(crate) fn new() -> Self {
+        Scripts {
+            fixed_window: script!("fixed_window"),
+            sliding_log: script!("sliding_log"),
+            sliding_window: script!("sliding_window"),
+            leaky_bucket: script!("leaky_bucket"),
+            leaky_bucket_shape: script!("leaky_bucket_shape"),
+            token_bucket: script!("token_bucket"),
+            gcra: script!("gcra"),
+            refund: script!("refund"),
+            lease_acquire: script!("lease_acquire"),
+            lease_release: script!("lease_release"),
+            lease_renew: script!("lease_renew"),
+            limits: limits_script!(
+                "fixed_window",
+                "sliding_log",
+                "sliding_window",
+                "leaky_bucket",
+                "token_bucket",
+                "gcra"
+            ),
+            #[cfg(feature = "calendar")]
+            quota: script!("quota"),
+            time: Script::new(include_str!("scripts/time.lua")),
+        }
+    }
+
+    fn all(&self) -> Vec<&Script> {
+        let all = vec![
+            &self.fixed_window,
+            &self.sliding_log,
+            &self.sliding_window,
+            &self.leaky_bucket,
+            &self.leaky_bucket_shape,
+            &self.token_bucket,
+            &self.gcra,
+            &self.refund,
+            &self.lease_acquire,
+            &self.lease_release,
+            &self.lease_renew,
+            &self.limits,
+            &self.time,
+        ];
+        #[cfg(feature = "calendar")]
+        let all = [all, vec![&self.quota]].concat();
+
+        all
+    }
+
+    /// Loads all scripts by SCRIPT LOAD, so the following calls only have to send EVALSHA.
+    /// A script flushed later (e.g. by SCRIPT FLUSH or a restart) is re-loaded on NOSCRIPT.
+    pub(crate) fn load(&self, conn: &mut dyn ConnectionLike) -> redis::RedisResult<()> {
+        for script in self.all() {
+            script.prepare_invoke().load(conn)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads all scripts like [`Scripts::load`] over an async connection.
+    #[cfg(feature = "async")]
+    pub(crate) async fn load_async<C>(&self, conn: &mut C) -> redis::RedisResult<()>
+    where
+        C: redis::aio::ConnectionLike,
+    {
+        for script in self.all() {
+            script.prepare_invoke().load_async(conn).await?;
+        }
+
+        Ok(())
+    }
+
+    /// The invocation of a script on the keys of the subject `key`, followed by the key of the
+    /// extra units granted to it, with the arguments shared by all scripts: the current time
+    /// (see [`TimeSource::script_arg`]) and the period of the rule.
+    fn invocation<'a>(
+        script: &'a Script,
+        key: &str,
+        keys: &[String],
+        now: String,
+        rule: &Rule,
+    ) -> Result<ScriptInvocation<'a>, RateLimitError> {
+        validate_rule(rule)?;
+        let mut invocation = script.prepare_invoke();
+        for key in keys {
+            invocation.key(key);
+        }
+        invocation.key(grant_key(key));
+        invocation.arg(now).arg(rule.period_millis());
+
+        Ok(invocation)
+    }
+
+    /// Reads the clock of the Redis server holding the key, as `(secs, micros)`.
+    pub(crate) fn time(&self, key: &str) -> ScriptInvocation<'_> {
+        let mut invocation = self.time.prepare_invoke();
+        invocation.key(key);
+
+        invocation
+    }
+
+    /// The invocation of the script of the algorithm on the keys of the subject `key`, for a
+    /// request which costs `cost` units. See [`algorithm_keys`] and [`algorithm_args`].
+    fn algorithm(
+        &self,
+        algorithm: Algorithm,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        let script = match algorithm {
+            Algorithm::FixedWindow => &self.fixed_window,
+            Algorithm::SlidingLog => &self.sliding_log,
+            Algorithm::SlidingWindow => &self.sliding_window,
+            Algorithm::LeakyBucket => &self.leaky_bucket,
+            Algorithm::TokenBucket => &self.token_bucket,
+            Algorithm::Gcra => &self.gcra,
+        };
+        let mut invocation = script.prepare_invoke();
+        for key in algorithm_keys(algorithm, key) {
+            invocation.key(key);
+        }
+        invocation.arg(now);
+        for arg in algorithm_args(algorithm, rule, cost, mode)? {
+            invocation.arg(arg);
+        }
+
+        Ok(invocation)
+    }
+
+    /// The invocation of the script checking a request which costs `cost` units against every
+    /// limit, given by its algorithm, the key of its subject and its rule.
+    pub(crate) fn limits(
+        &self,
+        limits: &[(Algorithm, String, Rule)],
+        now: String,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        if limits.is_empty() {
+            return Err(RateLimitError::InvalidConfig(
+                "at least one limit must be checked".to_string(),
+            ));
+        }
+        // the algorithms of one subject would count on the same keys, e.g. two fixed windows
+        // starting at the same time, so a request would be recorded twice on them
+        for (i, (_, key, _)) in limits.iter().enumerate() {
+            if limits[..i].iter().any(|(_, earlier, _)| earlier == key) {
+                return Err(RateLimitError::InvalidConfig(format!(
+                    "the subject {key} can only be limited once in a check, several windows of it \
+                     are checked by record_multi_window"
+                )));
+            }
+        }
+        let limits = limits
+            .iter()
+            .map(|(algorithm, key, rule)| {
+                let args = algorithm_args(*algorithm, rule, cost, mode)?;
+                Ok((*algorithm, algorithm_keys(*algorithm, key), args))
+            })
+            .collect::<Result<Vec<_>, RateLimitError>>()?;
+
+        Ok(self.limits_invocation(&limits, now, mode))
+    }
+
+    /// The invocation of the script checking a request which costs `cost` units against every
+    /// window of the rule on the subject `key`, each counted by the window algorithm on its own
+    /// keys. The extra units granted to the subject raise the limit of every window.
+    pub(crate) fn multi_window(
+        &self,
+        algorithm: Algorithm,
+        key: &str,
+        now: String,
+        rule: &MultiWindowRule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        validate_multi_window(algorithm, rule)?;
+        let windows = rule
+            .windows
+            .iter()
+            .map(|window| {
+                let args = algorithm_args(algorithm, window, cost, mode)?;
+                let mut keys = algorithm_keys(algorithm, &multi_window_key(key, window));
+                if let Some(grant) = keys.last_mut() {
+                    *grant = grant_key(key);
+                }
+                Ok((algorithm, keys, args))
+            })
+            .collect::<Result<Vec<_>, RateLimitError>>()?;
+
+        Ok(self.limits_invocation(&windows, now, mode))
+    }
+
+    /// The invocation of the script checking several limits, given by their algorithm, the keys
+    /// and the arguments (after the current time) of the script of the algorithm.
+    fn limits_invocation(
+        &self,
+        limits: &[(Algorithm, Vec<String>, Vec<String>)],
+        now: String,
+        mode: Mode,
+    ) -> ScriptInvocation<'_> {
+        let mut invocation = self.limits.prepare_invoke();
+        invocation.arg(now).arg(mode.script_arg()).arg(limits.len());
+        for (algorithm, keys, args) in limits {
+            for key in keys {
+                invocation.key(key);
+            }
+            invocation
+                .arg(algorithm.to_string())
+                .arg(keys.len())
+                .arg(args.len())
+                .arg(args);
+        }
+
+        invocation
+    }
+
+    pub(crate) fn fixed_window(
+        &self,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        self.algorithm(Algorithm::FixedWindow, key, now, rule, cost, mode)
+    }
+
+    pub(crate) fn sliding_log(
+        &self,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        self.algorithm(Algorithm::SlidingLog, key, now, rule, cost, mode)
+    }
+
+    pub(crate) fn sliding_window(
+        &self,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        self.algorithm(Algorithm::SlidingWindow, key, now, rule, cost, mode)
+    }
+
+    pub(crate) fn leaky_bucket(
+        &self,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        self.algorithm(Algorithm::LeakyBucket, key, now, rule, cost, mode)
+    }
+
+    pub(crate) fn leaky_bucket_shape(
+        &self,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        let mut invocation =
+            Scripts::invocation(&self.leaky_bucket_shape, key, &[key.to_string()], now, rule)?;
+        invocation
+            .arg(rule.burst())
+            .arg(max_delay.as_millis() as u64)
+            .arg(rule.limit);
+
+        Ok(invocation)
+    }
+
+    pub(crate) fn token_bucket(
+        &self,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        self.algorithm(Algorithm::TokenBucket, key, now, rule, cost, mode)
+    }
+
+    pub(crate) fn gcra(
+        &self,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        self.algorithm(Algorithm::Gcra, key, now, rule, cost, mode)
+    }
+
+    /// Gives back the units of a request recorded in [`Mode::Refundable`], by its receipt.
+    pub(crate) fn refund(
+        &self,
+        algorithm: Algorithm,
+        key: &str,
+        now: String,
+        rule: &Rule,
+        cost: u64,
+        receipt: &str,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        let mut keys = vec![refund_key(key, receipt), reset_key(key)];
+        match algorithm {
+            Algorithm::FixedWindow | Algorithm::SlidingWindow | Algorithm::LeakyBucket => {
+                keys.push(key.to_string())
+            }
+            Algorithm::SlidingLog => keys.extend(sliding_log_keys(key)),
+            Algorithm::TokenBucket => keys.extend(token_bucket_keys(key)),
+            Algorithm::Gcra => keys.push(gcra_key(key)),
+        }
+        let mut invocation = Scripts::invocation(&self.refund, key, &keys, now, rule)?;
+        invocation
+            .arg(algorithm.to_string())
+            .arg(cost)
+            .arg(rule.limit)
+            .arg(rule.burst())
+            .arg(receipt);
+
+        Ok(invocation)
+    }
+
+    /// Checks a request against the calendar quota, in the period holding `now`.
+    #[cfg(feature = "calendar")]
+    pub(crate) fn quota(
+        &self,
+        key: &str,
+        now: Duration,
+        quota: &Quota,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        let (start, end) = quota.bounds(now)?;
+        validate_cost(cost, quota.limit)?;
+        let mut invocation = self.quota.prepare_invoke();
+        invocation
+            .key(quota_key(key, quota, start))
+            .key(grant_key(key))
+            .arg(now.as_millis() as u64)
+            .arg(end)
+            .arg(quota.limit)
+            .arg(mode.script_arg())
+            .arg(cost)
+            // the count is kept for another period after the period ends
+            .arg(end + (end - start));
+
+        Ok(invocation)
+    }
+
+    /// Acquires one of `limit` leases on the subject `key`, held for `ttl`.
+    pub(crate) fn lease_acquire(
+        &self,
+        key: &str,
+        now: String,
+        limit: u64,
+        ttl: Duration,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        if limit == 0 {
+            return Err(RateLimitError::InvalidConfig(
+                "the limit of the leases must be at least one".to_string(),
+            ));
+        }
+        validate_lease_ttl(ttl)?;
+        let mut invocation = self.lease_acquire.prepare_invoke();
+        invocation
+            .key(leases_key(key))
+            .key(lease_sequence_key(key))
+            .arg(now)
+            .arg(ttl.as_millis() as u64)
+            .arg(limit);
+
+        Ok(invocation)
+    }
+
+    pub(crate) fn lease_release(&self, key: &str, now: String, id: &str) -> ScriptInvocation<'_> {
+        let mut invocation = self.lease_release.prepare_invoke();
+        invocation.key(leases_key(key)).arg(now).arg(id);
+
+        invocation
+    }
+
+    pub(crate) fn lease_renew(
+        &self,
+        key: &str,
+        now: String,
+        id: &str,
+        ttl: Duration,
+    ) -> Result<ScriptInvocation<'_>, RateLimitError> {
+        validate_lease_ttl(ttl)?;
+        let mut invocation = self.lease_renew.prepare_invoke();
+        invocation
+            .key(leases_key(key))
+            .arg(now)
+            .arg(id)
+            .arg(ttl.as_millis() as u64);
+
+        Ok(invocation)
+    }
 }
 
 impl RateLimiterRedis {
-    pub fn open(redis_address: &str, limit_per_sec: u64) -> Result<Self, ()> {
-        let client = redis::Client::open(redis_address).map_err(|err| {
-            eprintln!("Error: could not open the connection to the Redis({redis_address}): {err}")
-        })?;
+    pub fn open(redis_address: &str) -> Result<Self, RateLimitError> {
+        let client = redis::Client::open(redis_address)?;
+        let mut conn = client.get_connection()?;
 
-        let conn = client.get_connection().map_err(|err| {
-            eprintln!("Error: client could not get the connection to the Redis: {err}")
-        })?;
+        let scripts = Scripts::new();
+        scripts
+            .load(&mut conn)
+            .map_err(RateLimitError::from_script)?;
 
         Ok(RateLimiterRedis {
             conn,
-            limit_per_sec,
+            replica: None,
+            time_source: TimeSource::default(),
+            clock: Arc::new(SystemClock),
+            scripts,
         })
     }
+}
+
+/// The rate limiter on a Redis Cluster. The keys of a subject are hash-tagged by the subject
+/// (e.g. `prefix:resource:{subject}:tat`), so they share a slot and the scripts stay atomic.
+#[cfg(feature = "cluster")]
+pub type RateLimiterRedisCluster = RateLimiterRedis<redis::cluster::ClusterConnection>;
+
+#[cfg(feature = "cluster")]
+impl RateLimiterRedisCluster {
+    /// Connects to the cluster by any of its nodes. The scripts are not loaded up front, since
+    /// SCRIPT LOAD is sent to every master, but on the first NOSCRIPT reply of each script.
+    pub fn open_cluster(nodes: &[&str]) -> Result<Self, RateLimitError> {
+        let client = redis::cluster::ClusterClient::new(nodes.to_vec())?;
+        let conn = client.get_connection()?;
+
+        Ok(RateLimiterRedis {
+            conn,
+            replica: None,
+            time_source: TimeSource::default(),
+            clock: Arc::new(SystemClock),
+            scripts: Scripts::new(),
+        })
+    }
+}
+
+/// The rate limiter on the master of a Redis monitored by Sentinel, which follows the master
+/// after a failover. See [`SentinelConnection`].
+#[cfg(feature = "sentinel")]
+pub type RateLimiterRedisSentinel = RateLimiterRedis<SentinelConnection>;
+
+#[cfg(feature = "sentinel")]
+impl RateLimiterRedisSentinel {
+    /// Connects to the master named `master_name` through the sentinels, e.g.
+    /// `redis://127.0.0.1:26379/`.
+    pub fn open_sentinel(sentinels: &[&str], master_name: &str) -> Result<Self, RateLimitError> {
+        RateLimiterRedis::open_sentinel_with_config(
+            sentinels,
+            master_name,
+            SentinelConfig::default(),
+        )
+    }
+
+    pub fn open_sentinel_with_config(
+        sentinels: &[&str],
+        master_name: &str,
+        config: SentinelConfig,
+    ) -> Result<Self, RateLimitError> {
+        let mut conn = SentinelConnection::master(sentinels, master_name, &config.redis)?;
+        let mut replica = if config.read_from_replicas {
+            Some(SentinelConnection::replica(
+                sentinels,
+                master_name,
+                &config.redis,
+            )?)
+        } else {
+            None
+        };
+
+        let scripts = Scripts::new();
+        for conn in std::iter::once(&mut conn).chain(replica.as_mut()) {
+            scripts.load(conn).map_err(RateLimitError::from_script)?;
+        }
+
+        Ok(RateLimiterRedis {
+            conn,
+            replica,
+            time_source: TimeSource::default(),
+            clock: Arc::new(SystemClock),
+            scripts,
+        })
+    }
+}
+
+impl<C: ConnectionLike> RateLimiterRedis<C> {
+    /// Returns the current time since the Unix epoch from the configured time source, read on
+    /// the server holding the key when the server clock is used.
+    pub(crate) fn now(&mut self, key: &str) -> Result<Duration, RateLimitError> {
+        match self.time_source {
+            TimeSource::Server => {
+                let (secs, micros): (u64, u64) = self
+                    .scripts
+                    .time(key)
+                    .invoke(&mut self.conn)
+                    .map_err(RateLimitError::from_clock)?;
+
+                Ok(Duration::from_secs(secs) + Duration::from_micros(micros))
+            }
+            TimeSource::Client => self.clock.now(),
+        }
+    }
 
     pub fn record_fixed_window(
         &mut self,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<bool, ()> {
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        let window = (now.as_secs() / size.as_secs()) * size.as_secs();
-        let key = format!("{}:{}:{}:{}", key_prefix, resource, subject, window);
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
 
-        let (curr_count,): (Option<u64>,) = redis::pipe()
-            .atomic()
-            .get(&key)
-            .query(&mut self.conn)
-            .map_err(|err| {
-                eprintln!("Error: could not get the current requests number in fixed window: {err}")
-            })?;
+    /// Checks a request which costs `cost` units (e.g. the rows of a bulk export or the tokens
+    /// of an LLM call) instead of one, and records all of them if at least `cost` units remain.
+    /// The decision tells the units which remain. A cost of zero or above the limit (the burst
+    /// for the buckets and GCRA) is an [`RateLimitError::InvalidConfig`], even while extra units
+    /// are granted to the subject.
+    pub fn record_fixed_window_weighted(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
 
-        match curr_count {
-            Some(count) => {
-                if count >= self.limit_per_sec * size.as_secs() {
-                    return Ok(false);
-                }
-            }
-            None => {
-                redis::pipe()
-                    .atomic()
-                    .set(&key, 0)
-                    .query(&mut self.conn)
-                    .map_err(|err| {
-                        eprintln!("Error: could not initiate the fixed window: {err}")
-                    })?;
-            }
-        };
+    pub fn fetch_fixed_window(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Returns exactly the decision [`RateLimiterRedis::record_fixed_window_weighted`] would make
+    /// for a request of `cost` units right now, without recording it or changing any key. Unlike
+    /// [`RateLimiterRedis::fetch_fixed_window`], `remaining` is what would be left after the request.
+    pub fn peek_fixed_window(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
 
-        redis::pipe()
-            .atomic()
-            .incr(&key, 1)
-            .expire(&key, size.as_secs() as usize)
-            .ignore()
-            .query(&mut self.conn)
-            .map_err(|err| eprintln!("Error: could not set the key-value into Redis when using fixed window method: {err}"))?;
+    pub(crate) fn fixed_window<T: FromRedisValue>(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<T, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
 
-        Ok(true)
+        self.scripts
+            .fixed_window(&key, now, rule, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)
     }
 
-    pub fn fetch_fixed_window(
+    pub fn reset_fixed_window(
         &mut self,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<u64, ()> {
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        let window = (now.as_secs() / size.as_secs()) * size.as_secs();
-        let key = format!("{key_prefix}:{resource}:{subject}:{window}");
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now(&key)?;
 
-        let count: Option<u64> = self
-            .conn
-            .get(key)
-            .map_err(|err| eprintln!("Error: could not get the key from Redis: {err}"))?;
+        self.conn.del::<_, ()>(window_key(&key, rule, now, 0))?;
 
-        Ok(count.unwrap_or(0))
+        Ok(())
     }
 
     pub fn record_sliding_log(
@@ -93,48 +733,76 @@ impl RateLimiterRedis {
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<bool, ()> {
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        let key = format!("{key_prefix}:{resource}:{subject}");
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
 
-        let count: u64 = self.conn.zcard(&key).map_err(|err| {
-            eprintln!("Error: could not fetch the value of key: {key}: {err}");
-        })?;
+    /// Like [`RateLimiterRedis::record_sliding_log`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedis::record_fixed_window_weighted`].
+    pub fn record_sliding_log_weighted(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
 
-        if count >= self.limit_per_sec * size.as_secs() {
-            return Ok(false);
-        }
+    pub fn fetch_sliding_log(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedis::peek_fixed_window`], for
+    /// [`RateLimiterRedis::record_sliding_log_weighted`].
+    pub fn peek_sliding_log(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
 
-        let (_count,): (u64,) = redis::pipe()
-            .atomic()
-            .zrembyscore(&key, 0, (now.as_millis() - size.as_millis()) as u64)
-            .ignore()
-            .zadd(&key, now.as_millis() as u64, now.as_millis() as u64)
-            .ignore()
-            .zcard(&key)
-            .expire(&key, size.as_secs() as usize)
-            .ignore()
-            .query(&mut self.conn)
-            .map_err(|err| {
-                eprintln!("Error: could not set the key-value by sliding log method: {err}")
-            })?;
+    pub(crate) fn sliding_log<T: FromRedisValue>(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<T, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
 
-        Ok(true)
+        self.scripts
+            .sliding_log(&key, now, rule, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)
     }
 
-    pub fn fetch_sliding_log(
+    pub fn reset_sliding_log(
         &mut self,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-    ) -> Result<u64, ()> {
-        let key = format!("{key_prefix}:{resource}:{subject}");
-        let count: u64 = self.conn.zcard(&key).map_err(|err| {
-            eprintln!("Error: could not fetch the value of key: {key}: {err}");
-        })?;
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
 
-        Ok(count)
+        self.conn.del::<_, ()>(&sliding_log_keys(&key))?;
+
+        Ok(())
     }
 
     pub fn record_sliding_window(
@@ -142,269 +810,672 @@ impl RateLimiterRedis {
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<bool, ()> {
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        let current_window = (now.as_secs() / size.as_secs()) * size.as_secs();
-        let current_key = format!("{key_prefix}:{resource}:{subject}:{current_window}");
-        let previous_window = (now.as_secs() / size.as_secs()) * size.as_secs() - size.as_secs();
-        let previous_key = format!("{key_prefix}:{resource}:{subject}:{previous_window}");
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterRedis::record_sliding_window`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedis::record_fixed_window_weighted`].
+    pub fn record_sliding_window_weighted(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_sliding_window(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedis::peek_fixed_window`], for
+    /// [`RateLimiterRedis::record_sliding_window_weighted`].
+    pub fn peek_sliding_window(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    pub(crate) fn sliding_window<T: FromRedisValue>(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<T, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.scripts
+            .sliding_window(&key, now, rule, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)
+    }
+
+    pub fn reset_sliding_window(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now(&key)?;
+
+        self.conn.del::<_, ()>(&[
+            window_key(&key, rule, now, 0),
+            window_key(&key, rule, now, 1),
+        ])?;
+
+        Ok(())
+    }
 
-        let (previous_count, current_count): (Option<u64>, Option<u64>) = self
-            .conn
-            .get(vec![&previous_key, &current_key])
-            .map_err(|err| {
-                eprintln!("Error: could not fetch the key-value in fetch sliding window: {err}")
-            })?;
+    pub fn record_leaky_bucket(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
 
-        let count = Self::sliding_window_counter(previous_count, current_count, now, size);
+    /// Like [`RateLimiterRedis::record_leaky_bucket`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedis::record_fixed_window_weighted`].
+    pub fn record_leaky_bucket_weighted(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_leaky_bucket(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedis::peek_fixed_window`], for
+    /// [`RateLimiterRedis::record_leaky_bucket_weighted`].
+    pub fn peek_leaky_bucket(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    pub(crate) fn leaky_bucket<T: FromRedisValue>(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<T, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.scripts
+            .leaky_bucket(&key, now, rule, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)
+    }
+
+    /// Shapes the traffic instead of rejecting it: schedules the request into the bucket and
+    /// returns when it may be processed, i.e. when it has leaked out at the rate of the rule.
+    /// A request which would wait longer than `max_delay` is not scheduled.
+    pub fn schedule_leaky_bucket(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<Schedule, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
 
-        if count >= self.limit_per_sec * size.as_secs() {
-            return Ok(false);
+        self.scripts
+            .leaky_bucket_shape(&key, now, rule, max_delay)?
+            .invoke(&mut self.conn)
+            .map_err(RateLimitError::from_script)
+    }
+
+    /// Schedules the request like [`RateLimiterRedis::schedule_leaky_bucket`], and blocks
+    /// until it may be processed if it was scheduled.
+    pub fn wait_leaky_bucket(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<Schedule, RateLimitError> {
+        let schedule =
+            self.schedule_leaky_bucket(key_prefix, resource, subject, rule, max_delay)?;
+        if schedule.scheduled {
+            std::thread::sleep(schedule.delay);
         }
 
-        let (_previous_count, _current_count): (Option<u64>, Option<u64>) = redis::pipe()
-            .atomic()
-            .get(&previous_key)
-            .incr(&current_key, 1)
-            .expire(&current_key, (size.as_secs() * 2) as usize)
-            .ignore()
-            .query(&mut self.conn)
-            .map_err(|err| {
-                eprintln!("Error: could not set the key-value in record sliding window: {err}")
-            })?;
+        Ok(schedule)
+    }
+
+    pub fn reset_leaky_bucket(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.conn.del::<_, ()>(key)?;
 
-        return Ok(true);
+        Ok(())
     }
 
-    fn sliding_window_counter(
-        previous_count: Option<u64>,
-        current_count: Option<u64>,
-        now: Duration,
-        size: Duration,
-    ) -> u64 {
-        let current_window = (now.as_secs() / size.as_secs()) * size.as_secs();
-        let next_window = current_window + size.as_secs();
-        /*
-         *  pre win  curr win
-         * |  size  |  size  |
-         * -------------------
-         *      ^__size__^
-         *      ^---^    ^---^
-         *    section1 = section2 (weight1 = weight2)
-         **/
-        let weight = (Duration::from_secs(next_window).as_millis() - now.as_millis()) as f64
-            / size.as_millis() as f64;
-
-        current_count.unwrap_or(0) + (previous_count.unwrap_or(0) as f64 * weight).round() as u64
+    pub fn record_token_bucket(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, 1, Mode::Record)
     }
 
-    pub fn fetch_sliding_window(
+    /// Like [`RateLimiterRedis::record_token_bucket`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedis::record_fixed_window_weighted`].
+    pub fn record_token_bucket_weighted(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_token_bucket(
         &mut self,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<u64, ()> {
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        let current_window = (now.as_secs() / size.as_secs()) * size.as_secs();
-        let current_key = format!("{key_prefix}:{resource}:{subject}:{current_window}");
-        let previous_window = (now.as_secs() / size.as_secs()) * size.as_secs() - size.as_secs();
-        let previous_key = format!("{key_prefix}:{resource}:{subject}:{previous_window}");
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
 
-        let (previous_count, current_count): (Option<u64>, Option<u64>) = self
-            .conn
-            .get(vec![previous_key, current_key])
-            .map_err(|err| {
-                eprintln!("Error: could not fetch the key-value in fetch sliding window: {err}")
-            })?;
+    /// Like [`RateLimiterRedis::peek_fixed_window`], for
+    /// [`RateLimiterRedis::record_token_bucket_weighted`].
+    pub fn peek_token_bucket(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
 
-        Ok(Self::sliding_window_counter(
-            previous_count,
-            current_count,
-            now,
-            size,
-        ))
+    pub(crate) fn token_bucket<T: FromRedisValue>(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<T, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.scripts
+            .token_bucket(&key, now, rule, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)
     }
 
-    pub fn consume_leaky_bucket(
+    pub fn reset_token_bucket(
         &mut self,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<(), ()> {
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        let curr_window = (now.as_secs() / size.as_secs()) * size.as_secs();
-        let next_window = curr_window + size.as_secs();
-        let key = format!("{key_prefix}:{resource}:{subject}");
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
 
-        redis::pipe()
-            .ltrim(&key, curr_window as isize, next_window as isize)
-            .query(&mut self.conn)
-            .map_err(|err| eprintln!("ERROR: could not consume the element in the queue: {err}"))?;
+        self.conn.del::<_, ()>(&token_bucket_keys(&key))?;
 
         Ok(())
     }
 
-    pub fn record_leaky_bucket(
+    /// GCRA (generic cell rate algorithm): a token bucket with `rule.burst()` capacity refilled
+    /// at `rule.limit` per `rule.period`, which keeps only the theoretical arrival time of the
+    /// next request in one key per subject.
+    pub fn record_gcra(
         &mut self,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<bool, ()> {
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        let key = format!("{key_prefix}:{resource}:{subject}");
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
 
-        let (count,): (Option<u64>,) = redis::pipe()
-            .atomic()
-            .llen(&key)
-            .query(&mut self.conn)
-            .map_err(|err| {
-                eprintln!("ERROR: could not get the element number in the queue: {err}")
-            })?;
+    /// Like [`RateLimiterRedis::record_gcra`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedis::record_fixed_window_weighted`].
+    pub fn record_gcra_weighted(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
 
-        if count.unwrap_or(0) >= self.limit_per_sec * size.as_secs() {
-            return Ok(false);
-        }
+    pub fn fetch_gcra(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
 
-        let (_count,): (Option<u64>,) = redis::pipe()
-            .atomic()
-            .lpush(&key, now.as_secs() as isize)
-            .expire(&key, size.as_secs() as usize)
-            .ignore()
-            .query(&mut self.conn)
-            .map_err(|err| {
-                eprintln!("ERROR: could not increase the element in the queue: {err}")
-            })?;
+    /// Like [`RateLimiterRedis::peek_fixed_window`], for
+    /// [`RateLimiterRedis::record_gcra_weighted`].
+    pub fn peek_gcra(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    pub(crate) fn gcra<T: FromRedisValue>(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<T, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
 
-        Ok(true)
+        self.scripts
+            .gcra(&key, now, rule, cost, mode)?
+            .invoke(route(&mut self.conn, &mut self.replica, mode))
+            .map_err(RateLimitError::from_script)
     }
 
-    pub fn fetch_leaky_bucket(
+    pub fn reset_gcra(
         &mut self,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<u64, ()> {
-        let key = format!("{key_prefix}:{resource}:{subject}");
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
 
-        let (count,): (Option<u64>,) = redis::pipe()
-            .atomic()
-            .llen(&key)
-            .query(&mut self.conn)
-            .map_err(|err| {
-                eprintln!("ERROR: could not get the element number in the queue: {err}")
-            })?;
+        self.conn.del::<_, ()>(gcra_key(&key))?;
 
-        Ok(count.unwrap_or(0))
+        Ok(())
     }
+}
 
-    pub fn record_token_bucket(
+/// Runs the algorithms on Redis, so the limits are shared by every process using it.
+impl<C: ConnectionLike> Backend for RateLimiterRedis<C> {
+    fn record_weighted(
         &mut self,
+        algorithm: Algorithm,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<bool, ()> {
-        let key = format!("{key_prefix}:{resource}:{subject}");
-        let last_set_time_key = format!("{key}:last_set_time");
-        let remain_req_key = format!("{key}:remain_requests");
-        let (last_set_time,): (Option<u64>,) = redis::pipe()
-            .atomic()
-            .get(&last_set_time_key)
-            .query(&mut self.conn)
-            .map_err(|err| eprintln!("Error: could not get the last setting time: {err}"))?;
-
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        match last_set_time {
-            Some(last_time) => {
-                if now.as_secs() - last_time >= size.as_secs() {
-                    redis::pipe()
-                        .atomic()
-                        .set(&remain_req_key, &self.limit_per_sec * size.as_secs())
-                        .expire(&remain_req_key, size.as_secs() as usize)
-                        .set(&last_set_time_key, now.as_secs())
-                        .expire(&last_set_time_key, size.as_secs() as usize)
-                        .query(&mut self.conn)
-                        .map_err(|err| {
-                            eprintln!("Error: could not re-set the remain request by keys: {remain_req_key} and {last_set_time_key}: {err}")
-                        })?;
-                } else {
-                    let (remain_requests,): (u64,) = redis::pipe()
-                        .atomic()
-                        .get(&remain_req_key)
-                        .query(&mut self.conn)
-                        .map_err(|err| {
-                            eprintln!("Error: could not get the remain requests by keys: {remain_req_key} and {last_set_time_key}: {err}")
-                        })?;
-
-                    if remain_requests <= 0 {
-                        return Ok(false);
-                    }
-                }
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        match algorithm {
+            Algorithm::FixedWindow => {
+                self.record_fixed_window_weighted(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::SlidingLog => {
+                self.record_sliding_log_weighted(key_prefix, resource, subject, rule, cost)
             }
-            None => {
-                redis::pipe()
-                    .atomic()
-                    .set(&last_set_time_key, now.as_secs())
-                    .expire(&last_set_time_key, size.as_secs() as usize)
-                    .set(&remain_req_key, &self.limit_per_sec * size.as_secs())
-                    .expire(&remain_req_key, size.as_secs() as usize)
-                    .query(&mut self.conn)
-                    .map_err(|err| {
-                        eprintln!(
-                            "Error: could not initiate the first request in token bucket: {err}"
-                        )
-                    })?;
+            Algorithm::SlidingWindow => {
+                self.record_sliding_window_weighted(key_prefix, resource, subject, rule, cost)
             }
+            Algorithm::LeakyBucket => {
+                self.record_leaky_bucket_weighted(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::TokenBucket => {
+                self.record_token_bucket_weighted(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::Gcra => self.record_gcra_weighted(key_prefix, resource, subject, rule, cost),
         }
+    }
 
-        redis::pipe()
-            .atomic()
-            .decr(remain_req_key, 1)
-            .query(&mut self.conn)
-            .map_err(|err| eprintln!("Error: could not decrease the value: {err}"))?;
+    fn fetch(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        match algorithm {
+            Algorithm::FixedWindow => self.fetch_fixed_window(key_prefix, resource, subject, rule),
+            Algorithm::SlidingLog => self.fetch_sliding_log(key_prefix, resource, subject, rule),
+            Algorithm::SlidingWindow => {
+                self.fetch_sliding_window(key_prefix, resource, subject, rule)
+            }
+            Algorithm::LeakyBucket => self.fetch_leaky_bucket(key_prefix, resource, subject, rule),
+            Algorithm::TokenBucket => self.fetch_token_bucket(key_prefix, resource, subject, rule),
+            Algorithm::Gcra => self.fetch_gcra(key_prefix, resource, subject, rule),
+        }
+    }
 
-        Ok(true)
+    fn peek(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        match algorithm {
+            Algorithm::FixedWindow => {
+                self.peek_fixed_window(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::SlidingLog => {
+                self.peek_sliding_log(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::SlidingWindow => {
+                self.peek_sliding_window(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::LeakyBucket => {
+                self.peek_leaky_bucket(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::TokenBucket => {
+                self.peek_token_bucket(key_prefix, resource, subject, rule, cost)
+            }
+            Algorithm::Gcra => self.peek_gcra(key_prefix, resource, subject, rule, cost),
+        }
     }
 
-    pub fn fetch_token_bucket(
+    fn reset(
         &mut self,
+        algorithm: Algorithm,
         key_prefix: &str,
         resource: &str,
         subject: &str,
-        size: Duration,
-    ) -> Result<u64, ()> {
-        let key = format!("{key_prefix}:{resource}:{subject}");
-        let last_set_time_key = format!("{key}:last_set_time");
-        let remain_req_key = format!("{key}:remain_requests");
-        let (last_set_time,): (Option<u64>,) = redis::pipe()
-            .atomic()
-            .get(&last_set_time_key)
-            .query(&mut self.conn)
-            .map_err(|err| eprintln!("Error: could not get the last setting time: {err}"))?;
-
-        let now = SystemTime::now().duration_since(time::UNIX_EPOCH).unwrap();
-        match last_set_time {
-            Some(last_time) => {
-                if now.as_secs() - last_time >= size.as_secs() {
-                    Ok(self.limit_per_sec * size.as_secs())
-                } else {
-                    let (remain_requests,): (u64,) = redis::pipe()
-                        .atomic()
-                        .get(&remain_req_key)
-                        .query(&mut self.conn)
-                        .map_err(|err| {
-                            eprintln!("Error: could not get the remain requests by keys: {remain_req_key} and {last_set_time_key}: {err}")
-                        })?;
-
-                    Ok(remain_requests)
-                }
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        match algorithm {
+            Algorithm::FixedWindow => self.reset_fixed_window(key_prefix, resource, subject, rule),
+            Algorithm::SlidingLog => self.reset_sliding_log(key_prefix, resource, subject),
+            Algorithm::SlidingWindow => {
+                self.reset_sliding_window(key_prefix, resource, subject, rule)
             }
-            None => Ok(self.limit_per_sec * size.as_secs()),
+            Algorithm::LeakyBucket => self.reset_leaky_bucket(key_prefix, resource, subject),
+            Algorithm::TokenBucket => self.reset_token_bucket(key_prefix, resource, subject),
+            Algorithm::Gcra => self.reset_gcra(key_prefix, resource, subject),
+        }
+    }
+}
+
+/// The connection a script is run on: the replica, if any, when the script only fetches the
+/// current state. A peek runs on the master, so it decides exactly like the next record.
+pub(crate) fn route<'a, C: ConnectionLike>(
+    conn: &'a mut C,
+    replica: &'a mut Option<C>,
+    mode: Mode,
+) -> &'a mut dyn ConnectionLike {
+    match replica {
+        Some(replica) if mode == Mode::Fetch => replica,
+        _ => conn,
+    }
+}
+
+/// The key of the subject, which the keys of every algorithm are derived from. The subject is
+/// wrapped in a hash tag, so all keys of a subject share a slot on a Redis Cluster.
+pub(crate) fn subject_key(key_prefix: &str, resource: &str, subject: &str) -> String {
+    format!("{key_prefix}:{resource}:{{{subject}}}")
+}
+
+/// The keys of the script of the algorithm on the subject `key`, the last one being the key of
+/// the extra units granted to the subject.
+pub(crate) fn algorithm_keys(algorithm: Algorithm, key: &str) -> Vec<String> {
+    let mut keys = match algorithm {
+        Algorithm::FixedWindow | Algorithm::SlidingWindow | Algorithm::LeakyBucket => {
+            vec![key.to_string(), sequence_key(key)]
         }
+        Algorithm::SlidingLog => sliding_log_keys(key).to_vec(),
+        Algorithm::TokenBucket => {
+            let [last_refill, tokens] = token_bucket_keys(key);
+            vec![last_refill, tokens, sequence_key(key)]
+        }
+        Algorithm::Gcra => vec![gcra_key(key), sequence_key(key)],
+    };
+    keys.push(grant_key(key));
+
+    keys
+}
+
+/// The arguments of the script of the algorithm after the current time: the period of the rule,
+/// its limit (the burst for the buckets), the mode, the cost of the request, and for the buckets
+/// and GCRA the other one of the limit and the burst.
+pub(crate) fn algorithm_args(
+    algorithm: Algorithm,
+    rule: &Rule,
+    cost: u64,
+    mode: Mode,
+) -> Result<Vec<String>, RateLimitError> {
+    validate_rule(rule)?;
+    let (capacity, extra) = match algorithm {
+        Algorithm::FixedWindow | Algorithm::SlidingLog | Algorithm::SlidingWindow => {
+            validate_cost(cost, rule.limit)?;
+            (rule.limit, None)
+        }
+        Algorithm::LeakyBucket | Algorithm::TokenBucket => {
+            validate_cost(cost, rule.burst())?;
+            (rule.burst(), Some(rule.limit))
+        }
+        Algorithm::Gcra => {
+            validate_cost(cost, rule.burst())?;
+            (rule.limit, Some(rule.burst()))
+        }
+    };
+    let mut args = vec![
+        rule.period_millis().to_string(),
+        capacity.to_string(),
+        mode.script_arg().to_string(),
+        cost.to_string(),
+    ];
+    args.extend(extra.map(|extra| extra.to_string()));
+
+    Ok(args)
+}
+
+/// The key of the fixed window at `now`, or of the one `back` windows before it. The windows
+/// before the Unix epoch start at negative times, like the scripts name them.
+pub(crate) fn window_key(key: &str, rule: &Rule, now: Duration, back: u64) -> String {
+    let size = rule.period_millis() as i128;
+    let window = (now.as_millis() as i128 / size - back as i128) * size;
+    format!("{key}:{window}")
+}
+
+/// The subject key of one window of a [`MultiWindowRule`], so the windows of the rule are
+/// counted apart.
+pub(crate) fn multi_window_key(key: &str, window: &Rule) -> String {
+    format!("{key}:{}ms", window.period_millis())
+}
+
+/// The sorted set of the logged requests and the sequence of its members.
+pub(crate) fn sliding_log_keys(key: &str) -> [String; 2] {
+    [key.to_string(), sequence_key(key)]
+}
+
+/// The sequence numbering the members of the sliding log and the receipts of refundable
+/// requests.
+pub(crate) fn sequence_key(key: &str) -> String {
+    format!("{key}:seq")
+}
+
+/// The last refill time and the remaining tokens of the bucket.
+pub(crate) fn token_bucket_keys(key: &str) -> [String; 2] {
+    [
+        format!("{key}:last_set_time"),
+        format!("{key}:remain_requests"),
+    ]
+}
+
+/// The theoretical arrival time of the next request.
+pub(crate) fn gcra_key(key: &str) -> String {
+    format!("{key}:tat")
+}
+
+/// The leases held on the subject, scored by when they expire.
+pub(crate) fn leases_key(key: &str) -> String {
+    format!("{key}:leases")
+}
+
+/// The sequence numbering the leases, apart from [`sequence_key`] so the TTLs of the leases
+/// never shorten the life of the sequence of the algorithms.
+pub(crate) fn lease_sequence_key(key: &str) -> String {
+    format!("{key}:lease_seq")
+}
+
+/// The marker of the refund of the request with the receipt, so it is refunded only once.
+pub(crate) fn refund_key(key: &str, receipt: &str) -> String {
+    format!("{key}:refund:{receipt}")
+}
+
+/// The sequence of the last receipt issued before the subject was reset, so the requests
+/// recorded before the reset are not refunded.
+pub(crate) fn reset_key(key: &str) -> String {
+    format!("{key}:reset")
+}
+
+/// The extra units granted to the subject for a while, shared by every algorithm.
+pub(crate) fn grant_key(key: &str) -> String {
+    format!("{key}:grant")
+}
+
+/// Checks the windows of the rule can be enforced together by the window algorithm.
+fn validate_multi_window(
+    algorithm: Algorithm,
+    rule: &MultiWindowRule,
+) -> Result<(), RateLimitError> {
+    if !matches!(
+        algorithm,
+        Algorithm::FixedWindow | Algorithm::SlidingLog | Algorithm::SlidingWindow
+    ) {
+        return Err(RateLimitError::InvalidConfig(format!(
+            "several windows can only be enforced by a window algorithm, got {algorithm}"
+        )));
     }
+    if rule.windows.is_empty() {
+        return Err(RateLimitError::InvalidConfig(
+            "at least one window must be checked".to_string(),
+        ));
+    }
+    for (i, window) in rule.windows.iter().enumerate() {
+        if rule.windows[..i]
+            .iter()
+            .any(|earlier| earlier.period_millis() == window.period_millis())
+        {
+            return Err(RateLimitError::InvalidConfig(format!(
+                "the windows must differ in size, got {:?} twice",
+                window.period
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the rule can be enforced by the algorithms.
+pub(crate) fn validate_rule(rule: &Rule) -> Result<(), RateLimitError> {
+    if rule.limit == 0 {
+        return Err(RateLimitError::InvalidConfig(
+            "the limit must be at least one".to_string(),
+        ));
+    }
+    if rule.period_millis() == 0 {
+        return Err(RateLimitError::InvalidConfig(format!(
+            "the period must be at least one millisecond, got {:?}",
+            rule.period
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks a request of the cost can ever be allowed by a limit or a bucket of `capacity`. The
+/// check is made before the script runs, so the units granted to the subject by
+/// [`RateLimiterRedis::grant_extra_quota`] do not raise the cost a request may have.
+pub(crate) fn validate_cost(cost: u64, capacity: u64) -> Result<(), RateLimitError> {
+    if cost == 0 {
+        return Err(RateLimitError::InvalidConfig(
+            "the cost must be at least one".to_string(),
+        ));
+    }
+    if cost > capacity {
+        return Err(RateLimitError::InvalidConfig(format!(
+            "the cost {cost} exceeds the capacity {capacity} of the rule"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks a lease is held for a while.
+fn validate_lease_ttl(ttl: Duration) -> Result<(), RateLimitError> {
+    if ttl.as_millis() == 0 {
+        return Err(RateLimitError::InvalidConfig(format!(
+            "the lease must last at least one millisecond, got {ttl:?}"
+        )));
+    }
+
+    Ok(())
 }
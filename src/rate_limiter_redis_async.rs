@@ -0,0 +1,596 @@
+use crate::clock::{Clock, SystemClock};
+use crate::decision::{Decision, Schedule};
+use crate::error::RateLimitError;
+use crate::rate_limiter_redis::{
+    gcra_key, sliding_log_keys, subject_key, token_bucket_keys, validate_rule, window_key, Mode,
+    Scripts, TimeSource,
+};
+use crate::rule::Rule;
+use redis::aio::MultiplexedConnection;
+use redis::{FromRedisValue, ScriptInvocation};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The async counterpart of [`RateLimiterRedis`](crate::rate_limiter_redis::RateLimiterRedis)
+/// on a multiplexed connection, so one limiter can be shared by many tasks (e.g. in an `Arc`)
+/// without blocking the executor or locking around it.
+pub struct RateLimiterRedisAsync {
+    conn: MultiplexedConnection,
+    pub time_source: TimeSource,
+    /// The clock read when `time_source` is [`TimeSource::Client`].
+    pub clock: Arc<dyn Clock>,
+    scripts: Scripts,
+}
+
+impl RateLimiterRedisAsync {
+    pub async fn open(redis_address: &str) -> Result<Self, RateLimitError> {
+        let client = redis::Client::open(redis_address)?;
+        let mut conn = client.get_multiplexed_tokio_connection().await?;
+
+        let scripts = Scripts::new();
+        scripts
+            .load_async(&mut conn)
+            .await
+            .map_err(RateLimitError::from_script)?;
+
+        Ok(RateLimiterRedisAsync {
+            conn,
+            time_source: TimeSource::default(),
+            clock: Arc::new(SystemClock),
+            scripts,
+        })
+    }
+
+    /// Returns the current time since the Unix epoch from the configured time source, read on
+    /// the server holding the key when the server clock is used.
+    async fn now(&self, key: &str) -> Result<Duration, RateLimitError> {
+        match self.time_source {
+            TimeSource::Server => {
+                let (secs, micros): (u64, u64) = self
+                    .scripts
+                    .time(key)
+                    .invoke_async(&mut self.conn.clone())
+                    .await
+                    .map_err(RateLimitError::from_clock)?;
+
+                Ok(Duration::from_secs(secs) + Duration::from_micros(micros))
+            }
+            TimeSource::Client => self.clock.now(),
+        }
+    }
+
+    /// Runs a script on a clone of the multiplexed connection, which shares its socket.
+    async fn invoke<T: FromRedisValue>(
+        &self,
+        invocation: ScriptInvocation<'_>,
+    ) -> Result<T, RateLimitError> {
+        invocation
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(RateLimitError::from_script)
+    }
+
+    async fn del(&self, keys: &[String]) -> Result<(), RateLimitError> {
+        redis::cmd("DEL")
+            .arg(keys)
+            .query_async::<_, ()>(&mut self.conn.clone())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn record_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, 1, Mode::Record)
+            .await
+    }
+
+    /// Checks a request which costs `cost` units (e.g. the rows of a bulk export or the tokens
+    /// of an LLM call) instead of one, and records all of them if at least `cost` units remain.
+    /// The decision tells the units which remain. A cost of zero or above the limit (the burst
+    /// for the buckets and GCRA) is an [`RateLimitError::InvalidConfig`].
+    pub async fn record_fixed_window_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, cost, Mode::Record)
+            .await
+    }
+
+    pub async fn fetch_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+            .await
+    }
+
+    /// Returns exactly the decision [`RateLimiterRedisAsync::record_fixed_window_weighted`] would make
+    /// for a request of `cost` units right now, without recording it or changing any key. Unlike
+    /// [`RateLimiterRedisAsync::fetch_fixed_window`], `remaining` is what would be left after the request.
+    pub async fn peek_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, cost, Mode::Peek)
+            .await
+    }
+
+    async fn fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.fixed_window(&key, now, rule, cost, mode)?)
+            .await
+    }
+
+    pub async fn reset_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now(&key).await?;
+
+        self.del(&[window_key(&key, rule, now, 0)]).await
+    }
+
+    pub async fn record_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, 1, Mode::Record)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::record_sliding_log`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisAsync::record_fixed_window_weighted`].
+    pub async fn record_sliding_log_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, cost, Mode::Record)
+            .await
+    }
+
+    pub async fn fetch_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::peek_fixed_window`], for
+    /// [`RateLimiterRedisAsync::record_sliding_log_weighted`].
+    pub async fn peek_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, cost, Mode::Peek)
+            .await
+    }
+
+    async fn sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.sliding_log(&key, now, rule, cost, mode)?)
+            .await
+    }
+
+    pub async fn reset_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.del(&sliding_log_keys(&key)).await
+    }
+
+    pub async fn record_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, 1, Mode::Record)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::record_sliding_window`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisAsync::record_fixed_window_weighted`].
+    pub async fn record_sliding_window_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, cost, Mode::Record)
+            .await
+    }
+
+    pub async fn fetch_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::peek_fixed_window`], for
+    /// [`RateLimiterRedisAsync::record_sliding_window_weighted`].
+    pub async fn peek_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, cost, Mode::Peek)
+            .await
+    }
+
+    async fn sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.sliding_window(&key, now, rule, cost, mode)?)
+            .await
+    }
+
+    pub async fn reset_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now(&key).await?;
+
+        self.del(&[
+            window_key(&key, rule, now, 0),
+            window_key(&key, rule, now, 1),
+        ])
+        .await
+    }
+
+    pub async fn record_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, 1, Mode::Record)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::record_leaky_bucket`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisAsync::record_fixed_window_weighted`].
+    pub async fn record_leaky_bucket_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, cost, Mode::Record)
+            .await
+    }
+
+    pub async fn fetch_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::peek_fixed_window`], for
+    /// [`RateLimiterRedisAsync::record_leaky_bucket_weighted`].
+    pub async fn peek_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, cost, Mode::Peek)
+            .await
+    }
+
+    async fn leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.leaky_bucket(&key, now, rule, cost, mode)?)
+            .await
+    }
+
+    /// Shapes the traffic instead of rejecting it, see
+    /// [`RateLimiterRedis::schedule_leaky_bucket`](crate::rate_limiter_redis::RateLimiterRedis::schedule_leaky_bucket).
+    pub async fn schedule_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<Schedule, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(
+            self.scripts
+                .leaky_bucket_shape(&key, now, rule, max_delay)?,
+        )
+        .await
+    }
+
+    /// Schedules the request like [`RateLimiterRedisAsync::schedule_leaky_bucket`], and sleeps
+    /// until it may be processed if it was scheduled.
+    pub async fn wait_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<Schedule, RateLimitError> {
+        let schedule = self
+            .schedule_leaky_bucket(key_prefix, resource, subject, rule, max_delay)
+            .await?;
+        if schedule.scheduled {
+            tokio::time::sleep(schedule.delay).await;
+        }
+
+        Ok(schedule)
+    }
+
+    pub async fn reset_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.del(&[key]).await
+    }
+
+    pub async fn record_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, 1, Mode::Record)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::record_token_bucket`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisAsync::record_fixed_window_weighted`].
+    pub async fn record_token_bucket_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, cost, Mode::Record)
+            .await
+    }
+
+    pub async fn fetch_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::peek_fixed_window`], for
+    /// [`RateLimiterRedisAsync::record_token_bucket_weighted`].
+    pub async fn peek_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, cost, Mode::Peek)
+            .await
+    }
+
+    async fn token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.token_bucket(&key, now, rule, cost, mode)?)
+            .await
+    }
+
+    pub async fn reset_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.del(&token_bucket_keys(&key)).await
+    }
+
+    pub async fn record_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, 1, Mode::Record)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::record_gcra`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisAsync::record_fixed_window_weighted`].
+    pub async fn record_gcra_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, cost, Mode::Record)
+            .await
+    }
+
+    pub async fn fetch_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+            .await
+    }
+
+    /// Like [`RateLimiterRedisAsync::peek_fixed_window`], for
+    /// [`RateLimiterRedisAsync::record_gcra_weighted`].
+    pub async fn peek_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, cost, Mode::Peek)
+            .await
+    }
+
+    async fn gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.gcra(&key, now, rule, cost, mode)?)
+            .await
+    }
+
+    pub async fn reset_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.del(&[gcra_key(&key)]).await
+    }
+}
@@ -0,0 +1,596 @@
+use crate::clock::{Clock, SystemClock};
+use crate::decision::{Decision, Schedule};
+use crate::error::RateLimitError;
+use crate::rate_limiter_redis::{
+    gcra_key, sliding_log_keys, subject_key, token_bucket_keys, validate_rule, window_key, Mode,
+    Scripts, TimeSource,
+};
+use crate::rule::Rule;
+use redis::{Commands, FromRedisValue, ScriptInvocation};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How the connections of [`RateLimiterRedisPool`] are managed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// The maximum number of connections, 10 by default.
+    pub max_size: u32,
+    /// The number of idle connections kept open, `max_size` if not set.
+    pub min_idle: Option<u32>,
+    /// How long to wait for a connection before failing, 30s by default.
+    pub connection_timeout: Duration,
+    /// How long a connection may stay idle before it is closed, 10 minutes by default.
+    pub idle_timeout: Option<Duration>,
+    /// Whether a connection is checked by PING before it is used, true by default.
+    pub health_check: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 10,
+            min_idle: None,
+            connection_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            health_check: true,
+        }
+    }
+}
+
+/// A [`RateLimiterRedis`](crate::rate_limiter_redis::RateLimiterRedis) taking a connection
+/// from a pool for every call, so one limiter can be shared by many threads (e.g. in an
+/// `Arc`) without locking around it.
+pub struct RateLimiterRedisPool {
+    pool: r2d2::Pool<redis::Client>,
+    pub time_source: TimeSource,
+    /// The clock read when `time_source` is [`TimeSource::Client`].
+    pub clock: Arc<dyn Clock>,
+    scripts: Scripts,
+}
+
+impl RateLimiterRedisPool {
+    pub fn open(redis_address: &str) -> Result<Self, RateLimitError> {
+        RateLimiterRedisPool::open_with_config(redis_address, PoolConfig::default())
+    }
+
+    pub fn open_with_config(
+        redis_address: &str,
+        config: PoolConfig,
+    ) -> Result<Self, RateLimitError> {
+        let client = redis::Client::open(redis_address)?;
+        let pool = r2d2::Pool::builder()
+            .max_size(config.max_size)
+            .min_idle(config.min_idle)
+            .connection_timeout(config.connection_timeout)
+            .idle_timeout(config.idle_timeout)
+            .test_on_check_out(config.health_check)
+            .build(client)?;
+
+        let scripts = Scripts::new();
+        scripts
+            .load(&mut *pool.get()?)
+            .map_err(RateLimitError::from_script)?;
+
+        Ok(RateLimiterRedisPool {
+            pool,
+            time_source: TimeSource::default(),
+            clock: Arc::new(SystemClock),
+            scripts,
+        })
+    }
+
+    /// Returns the current time since the Unix epoch from the configured time source, read on
+    /// the server holding the key when the server clock is used.
+    fn now(&self, key: &str) -> Result<Duration, RateLimitError> {
+        match self.time_source {
+            TimeSource::Server => {
+                let (secs, micros): (u64, u64) = self
+                    .scripts
+                    .time(key)
+                    .invoke(&mut *self.pool.get()?)
+                    .map_err(RateLimitError::from_clock)?;
+
+                Ok(Duration::from_secs(secs) + Duration::from_micros(micros))
+            }
+            TimeSource::Client => self.clock.now(),
+        }
+    }
+
+    /// Runs a script on a connection taken from the pool.
+    fn invoke<T: FromRedisValue>(
+        &self,
+        invocation: ScriptInvocation<'_>,
+    ) -> Result<T, RateLimitError> {
+        invocation
+            .invoke(&mut *self.pool.get()?)
+            .map_err(RateLimitError::from_script)
+    }
+
+    fn del(&self, keys: &[String]) -> Result<(), RateLimitError> {
+        self.pool.get()?.del::<_, ()>(keys)?;
+
+        Ok(())
+    }
+
+    pub fn record_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Checks a request which costs `cost` units (e.g. the rows of a bulk export or the tokens
+    /// of an LLM call) instead of one, and records all of them if at least `cost` units remain.
+    /// The decision tells the units which remain. A cost of zero or above the limit (the burst
+    /// for the buckets and GCRA) is an [`RateLimitError::InvalidConfig`].
+    pub fn record_fixed_window_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Returns exactly the decision [`RateLimiterRedisPool::record_fixed_window_weighted`] would make
+    /// for a request of `cost` units right now, without recording it or changing any key. Unlike
+    /// [`RateLimiterRedisPool::fetch_fixed_window`], `remaining` is what would be left after the request.
+    pub fn peek_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.fixed_window(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    fn fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.fixed_window(&key, now, rule, cost, mode)?)
+    }
+
+    pub fn reset_fixed_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now(&key)?;
+
+        self.del(&[window_key(&key, rule, now, 0)])
+    }
+
+    pub fn record_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterRedisPool::record_sliding_log`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisPool::record_fixed_window_weighted`].
+    pub fn record_sliding_log_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedisPool::peek_fixed_window`], for
+    /// [`RateLimiterRedisPool::record_sliding_log_weighted`].
+    pub fn peek_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_log(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    fn sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.sliding_log(&key, now, rule, cost, mode)?)
+    }
+
+    pub fn reset_sliding_log(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.del(&sliding_log_keys(&key))
+    }
+
+    pub fn record_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterRedisPool::record_sliding_window`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisPool::record_fixed_window_weighted`].
+    pub fn record_sliding_window_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedisPool::peek_fixed_window`], for
+    /// [`RateLimiterRedisPool::record_sliding_window_weighted`].
+    pub fn peek_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.sliding_window(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    fn sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.sliding_window(&key, now, rule, cost, mode)?)
+    }
+
+    pub fn reset_sliding_window(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<(), RateLimitError> {
+        validate_rule(rule)?;
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.now(&key)?;
+
+        self.del(&[
+            window_key(&key, rule, now, 0),
+            window_key(&key, rule, now, 1),
+        ])
+    }
+
+    pub fn record_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterRedisPool::record_leaky_bucket`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisPool::record_fixed_window_weighted`].
+    pub fn record_leaky_bucket_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedisPool::peek_fixed_window`], for
+    /// [`RateLimiterRedisPool::record_leaky_bucket_weighted`].
+    pub fn peek_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.leaky_bucket(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    fn leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.leaky_bucket(&key, now, rule, cost, mode)?)
+    }
+
+    /// Shapes the traffic instead of rejecting it, see
+    /// [`RateLimiterRedis::schedule_leaky_bucket`](crate::rate_limiter_redis::RateLimiterRedis::schedule_leaky_bucket).
+    pub fn schedule_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<Schedule, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(
+            self.scripts
+                .leaky_bucket_shape(&key, now, rule, max_delay)?,
+        )
+    }
+
+    /// Schedules the request like [`RateLimiterRedisPool::schedule_leaky_bucket`], and blocks
+    /// until it may be processed if it was scheduled.
+    pub fn wait_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        max_delay: Duration,
+    ) -> Result<Schedule, RateLimitError> {
+        let schedule =
+            self.schedule_leaky_bucket(key_prefix, resource, subject, rule, max_delay)?;
+        if schedule.scheduled {
+            std::thread::sleep(schedule.delay);
+        }
+
+        Ok(schedule)
+    }
+
+    pub fn reset_leaky_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.del(&[key])
+    }
+
+    pub fn record_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterRedisPool::record_token_bucket`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisPool::record_fixed_window_weighted`].
+    pub fn record_token_bucket_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedisPool::peek_fixed_window`], for
+    /// [`RateLimiterRedisPool::record_token_bucket_weighted`].
+    pub fn peek_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.token_bucket(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    fn token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.token_bucket(&key, now, rule, cost, mode)?)
+    }
+
+    pub fn reset_token_bucket(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.del(&token_bucket_keys(&key))
+    }
+
+    pub fn record_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, 1, Mode::Record)
+    }
+
+    /// Like [`RateLimiterRedisPool::record_gcra`], for a request which costs `cost`
+    /// units, see [`RateLimiterRedisPool::record_fixed_window_weighted`].
+    pub fn record_gcra_weighted(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, cost, Mode::Record)
+    }
+
+    pub fn fetch_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, 1, Mode::Fetch)
+    }
+
+    /// Like [`RateLimiterRedisPool::peek_fixed_window`], for
+    /// [`RateLimiterRedisPool::record_gcra_weighted`].
+    pub fn peek_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<Decision, RateLimitError> {
+        self.gcra(key_prefix, resource, subject, rule, cost, Mode::Peek)
+    }
+
+    fn gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+        mode: Mode,
+    ) -> Result<Decision, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.invoke(self.scripts.gcra(&key, now, rule, cost, mode)?)
+    }
+
+    pub fn reset_gcra(
+        &self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+    ) -> Result<(), RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+
+        self.del(&[gcra_key(&key)])
+    }
+}
@@ -0,0 +1,113 @@
+use crate::decision::Decision;
+use crate::error::RateLimitError;
+use crate::rate_limiter::Algorithm;
+use crate::rate_limiter_redis::{subject_key, Mode, RateLimiterRedis};
+use crate::rule::Rule;
+use redis::{ConnectionLike, FromRedisValue, RedisResult, Value};
+
+/// The receipt of a request recorded by [`RateLimiterRedis::record_refundable`], which gives
+/// the units of the request back by [`RateLimiterRedis::refund`], e.g. when the downstream call
+/// it was allowed for failed before doing any work.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefundToken {
+    algorithm: Algorithm,
+    key: String,
+    rule: Rule,
+    cost: u64,
+    receipt: String,
+}
+
+impl RefundToken {
+    /// The units the request consumed, and a refund gives back.
+    pub fn cost(&self) -> u64 {
+        self.cost
+    }
+}
+
+/// Parses the reply of the scripts recording a refundable request: the decision, followed by
+/// the receipt of the request if it was allowed.
+struct Recorded {
+    decision: Decision,
+    receipt: Option<String>,
+}
+
+impl FromRedisValue for Recorded {
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        match v {
+            Value::Bulk(items) if items.len() == 6 => Ok(Recorded {
+                decision: Decision::from_redis_value(&Value::Bulk(items[..5].to_vec()))?,
+                receipt: Some(String::from_redis_value(&items[5])?),
+            }),
+            _ => Ok(Recorded {
+                decision: Decision::from_redis_value(v)?,
+                receipt: None,
+            }),
+        }
+    }
+}
+
+impl<C: ConnectionLike> RateLimiterRedis<C> {
+    /// Checks a request which costs `cost` units by the algorithm, like the `record_*_weighted`
+    /// methods, and returns the token to refund it by if it was allowed.
+    pub fn record_refundable(
+        &mut self,
+        algorithm: Algorithm,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        rule: &Rule,
+        cost: u64,
+    ) -> Result<(Decision, Option<RefundToken>), RateLimitError> {
+        let mode = Mode::Refundable;
+        let recorded: Recorded = match algorithm {
+            Algorithm::FixedWindow => {
+                self.fixed_window(key_prefix, resource, subject, rule, cost, mode)?
+            }
+            Algorithm::SlidingLog => {
+                self.sliding_log(key_prefix, resource, subject, rule, cost, mode)?
+            }
+            Algorithm::SlidingWindow => {
+                self.sliding_window(key_prefix, resource, subject, rule, cost, mode)?
+            }
+            Algorithm::LeakyBucket => {
+                self.leaky_bucket(key_prefix, resource, subject, rule, cost, mode)?
+            }
+            Algorithm::TokenBucket => {
+                self.token_bucket(key_prefix, resource, subject, rule, cost, mode)?
+            }
+            Algorithm::Gcra => self.gcra(key_prefix, resource, subject, rule, cost, mode)?,
+        };
+        let token = recorded.receipt.map(|receipt| RefundToken {
+            algorithm,
+            key: subject_key(key_prefix, resource, subject),
+            rule: *rule,
+            cost,
+            receipt,
+        });
+
+        Ok((recorded.decision, token))
+    }
+
+    /// Gives back the units consumed by the request of the token, in one atomic step: the
+    /// count of its window is decremented, its members are removed from the sliding log, or
+    /// the bucket gets its units back.
+    ///
+    /// Returns false, and gives nothing back, when the token has already been refunded, a
+    /// period of the rule has passed since the request, or the subject has been reset since,
+    /// so calling it twice is harmless.
+    pub fn refund(&mut self, token: &RefundToken) -> Result<bool, RateLimitError> {
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.scripts
+            .refund(
+                token.algorithm,
+                &token.key,
+                now,
+                &token.rule,
+                token.cost,
+                &token.receipt,
+            )?
+            .invoke(&mut self.conn)
+            .map_err(RateLimitError::from_script)
+    }
+}
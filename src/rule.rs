@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+/// A limit to enforce on a subject, e.g. "100 requests per 60s, burst 20".
+///
+/// Rules are passed to every check, so one client can enforce many differently-sized limits
+/// over the same connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    /// The number of requests allowed in a period.
+    pub limit: u64,
+    /// The size of the window, or the refill period of the buckets.
+    pub period: Duration,
+    /// The number of requests the buckets let through at once, `limit` if not set.
+    pub burst: Option<u64>,
+}
+
+impl Rule {
+    pub fn new(limit: u64, period: Duration) -> Self {
+        Rule {
+            limit,
+            period,
+            burst: None,
+        }
+    }
+
+    pub fn with_burst(self, burst: u64) -> Self {
+        Rule {
+            burst: Some(burst),
+            ..self
+        }
+    }
+
+    /// The period in whole milliseconds, the precision the algorithms work with.
+    pub(crate) fn period_millis(&self) -> u64 {
+        self.period.as_millis() as u64
+    }
+
+    /// The capacity of the buckets.
+    pub fn burst(&self) -> u64 {
+        self.burst.unwrap_or(self.limit)
+    }
+}
+
+/// Several windows enforced together on a subject, e.g. "10 per second and 500 per hour" to
+/// allow short bursts within a sustained rate. A request is allowed only if every window allows
+/// it, and is then counted in all of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultiWindowRule {
+    /// The limit and the size of every window, each of a different size.
+    pub windows: Vec<Rule>,
+}
+
+impl MultiWindowRule {
+    pub fn new() -> Self {
+        MultiWindowRule::default()
+    }
+
+    pub fn with_window(mut self, limit: u64, period: Duration) -> Self {
+        self.windows.push(Rule::new(limit, period));
+        self
+    }
+}
@@ -0,0 +1,10 @@
+-- ARGV[1]: now (millis) taken from the client, or empty to take it from the Redis server
+local function now_millis()
+    if ARGV[1] ~= '' then
+        return tonumber(ARGV[1])
+    end
+
+    local time = redis.call('TIME')
+    return tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+end
+
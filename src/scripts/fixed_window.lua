@@ -0,0 +1,31 @@
+-- KEYS[1]: key of the subject, KEYS[2]: sequence of the receipts
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the limit while they last
+-- ARGV[2]: window size (millis), ARGV[3]: limit, ARGV[4]: 'record' the request, 'refundable' to record it with a receipt, 'peek' at the decision it would get, or 'fetch' the current state
+-- ARGV[5]: cost of the request (at most the limit)
+local now = now_millis()
+local size = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3]) + tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+local mode = ARGV[4]
+local cost = tonumber(ARGV[5])
+
+local window = math.floor(now / size) * size
+local key = KEYS[1] .. ':' .. window
+local reset_at = window + size
+
+local count = tonumber(redis.call('GET', key) or '0')
+if count + cost > limit then
+    return {0, limit, math.max(limit - count, 0), reset_at, reset_at - now}
+end
+
+if mode == 'record' or mode == 'refundable' then
+    count = redis.call('INCRBY', key, cost)
+    redis.call('PEXPIRE', key, size)
+elseif mode == 'peek' then
+    count = count + cost
+end
+
+if mode == 'refundable' then
+    return {1, limit, limit - count, reset_at, 0, receipt(KEYS[2], now, size)}
+end
+
+return {1, limit, limit - count, reset_at, 0}
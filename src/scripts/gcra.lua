@@ -0,0 +1,44 @@
+-- KEYS[1]: theoretical arrival time (millis), KEYS[2]: sequence of the receipts
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the burst while they last
+-- ARGV[2]: period (millis), ARGV[3]: limit, ARGV[4]: 'record' the request, 'refundable' to record it with a receipt, 'peek' at the decision it would get, or 'fetch' the current state
+-- ARGV[5]: cost of the request (at most the burst), ARGV[6]: burst
+local now = now_millis()
+local period = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
+local mode = ARGV[4]
+local cost = tonumber(ARGV[5])
+local burst = tonumber(ARGV[6]) + tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+
+-- tolerance for the rounding of the interval, so a request is allowed right at its retry time
+local epsilon = 1e-9
+
+-- every request pushes the theoretical arrival time (TAT) one interval further per unit of its
+-- cost, and is allowed as long as the TAT it would set is at most `burst` intervals ahead of now
+local interval = period / limit
+local tolerance = interval * burst
+
+local tat = math.max(tonumber(redis.call('GET', KEYS[1])) or now, now)
+local new_tat = tat + interval * cost
+local allow_at = new_tat - tolerance
+
+-- the intervals left before the TAT is `burst` intervals ahead of now
+local function remaining()
+    return math.max(math.floor((now + tolerance - tat) / interval + epsilon), 0)
+end
+
+if allow_at > now + epsilon then
+    return {0, burst, math.min(remaining(), cost - 1), math.ceil(tat), math.ceil(allow_at - now - epsilon)}
+end
+
+if mode ~= 'fetch' then
+    tat = new_tat
+end
+if mode == 'record' or mode == 'refundable' then
+    redis.call('SET', KEYS[1], string.format('%.3f', tat), 'PX', math.ceil(tat - now))
+end
+
+if mode == 'refundable' then
+    return {1, burst, remaining(), math.ceil(tat), 0, receipt(KEYS[2], now, period)}
+end
+
+return {1, burst, remaining(), math.ceil(tat), 0}
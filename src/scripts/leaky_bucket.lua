@@ -0,0 +1,50 @@
+-- KEYS[1]: level and last leak time (millis) of the bucket, KEYS[2]: sequence of the receipts
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the capacity while they last
+-- ARGV[2]: leak period (millis), ARGV[3]: capacity (the burst of the rule), ARGV[4]: 'record' the request, 'refundable' to record it with a receipt, 'peek' at the decision it would get, or 'fetch' the current state
+-- ARGV[5]: cost of the request (at most the capacity), ARGV[6]: requests leaked per period (the limit of the rule)
+local now = now_millis()
+local period = tonumber(ARGV[2])
+local capacity = tonumber(ARGV[3]) + tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+local mode = ARGV[4]
+local cost = tonumber(ARGV[5])
+local leak = tonumber(ARGV[6])
+
+-- tolerance for the rounding of the leak, so a request is allowed right at its retry time
+local epsilon = 1e-9
+
+-- the bucket leaks continuously since the last call, down to empty
+local bucket = redis.call('HMGET', KEYS[1], 'level', 'last_leak')
+local level = tonumber(bucket[1]) or 0
+local last_leak = tonumber(bucket[2]) or now
+level = math.max(level - math.max(now - last_leak, 0) * leak / period, 0)
+
+-- the time (millis from now) until the bucket has leaked down to the given level
+local function time_until(target)
+    if level <= target + epsilon then
+        return 0
+    end
+    return math.ceil((level - target) * period / leak - epsilon)
+end
+
+-- the room left in the bucket, less than the cost when the request is denied
+local function remaining()
+    return math.floor(capacity - level + epsilon)
+end
+
+if level + cost > capacity + epsilon then
+    return {0, capacity, math.max(math.min(remaining(), cost - 1), 0), now + time_until(0), time_until(capacity - cost)}
+end
+
+if mode ~= 'fetch' then
+    level = level + cost
+end
+if mode == 'record' or mode == 'refundable' then
+    redis.call('HSET', KEYS[1], 'level', tostring(level), 'last_leak', now)
+    redis.call('PEXPIRE', KEYS[1], time_until(0))
+end
+
+if mode == 'refundable' then
+    return {1, capacity, remaining(), now + time_until(0), 0, receipt(KEYS[2], now, period)}
+end
+
+return {1, capacity, remaining(), now + time_until(0), 0}
@@ -0,0 +1,38 @@
+-- KEYS[1]: level and last leak time (millis) of the bucket
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the capacity while they last
+-- ARGV[2]: leak period (millis), ARGV[3]: capacity (the burst of the rule), ARGV[4]: max delay (millis)
+-- ARGV[5]: requests leaked per period (the limit of the rule)
+local now = now_millis()
+local period = tonumber(ARGV[2])
+local capacity = tonumber(ARGV[3]) + tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+local max_delay = tonumber(ARGV[4])
+local leak = tonumber(ARGV[5])
+
+-- tolerance for the rounding of the leak, so a request is scheduled right at its slot
+local epsilon = 1e-9
+
+-- the bucket leaks continuously since the last call, down to empty
+local bucket = redis.call('HMGET', KEYS[1], 'level', 'last_leak')
+local level = tonumber(bucket[1]) or 0
+local last_leak = tonumber(bucket[2]) or now
+level = math.max(level - math.max(now - last_leak, 0) * leak / period, 0)
+
+-- the time (millis from now) until the bucket has leaked down to the given level
+local function time_until(target)
+    if level <= target + epsilon then
+        return 0
+    end
+    return math.ceil((level - target) * period / leak - epsilon)
+end
+
+-- the request is queued above the capacity, and may be processed once there is room for it
+local delay = time_until(capacity - 1)
+if delay > max_delay then
+    return {0, now + delay - max_delay, delay - max_delay}
+end
+
+level = level + 1
+redis.call('HSET', KEYS[1], 'level', tostring(level), 'last_leak', now)
+redis.call('PEXPIRE', KEYS[1], time_until(0))
+
+return {1, now + delay, delay}
@@ -0,0 +1,27 @@
+-- KEYS[1]: leases held on the subject, scored by when they expire (millis), KEYS[2]: sequence of the lease IDs
+-- ARGV[2]: TTL of the lease (millis), ARGV[3]: maximum number of leases held at once
+local now = now_millis()
+local ttl = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3])
+
+-- the leases of crashed holders are freed once they expire
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now)
+local held = redis.call('ZCARD', KEYS[1])
+if held >= limit then
+    return false
+end
+
+local id = now .. ':' .. redis.call('INCR', KEYS[2])
+-- the sequence lives at least as long as the leases numbered by it, so the IDs never restart
+-- while a lease is held
+if redis.call('PTTL', KEYS[2]) < ttl then
+    redis.call('PEXPIRE', KEYS[2], ttl)
+end
+local expires_at = now + ttl
+redis.call('ZADD', KEYS[1], expires_at, id)
+
+-- the set lives as long as its last lease
+local last = redis.call('ZRANGE', KEYS[1], -1, -1, 'WITHSCORES')
+redis.call('PEXPIRE', KEYS[1], tonumber(last[2]) - now)
+
+return {id, expires_at, limit - held - 1}
@@ -0,0 +1,15 @@
+-- KEYS[1]: leases held on the subject, scored by when they expire (millis)
+-- ARGV[2]: ID of the lease
+local now = now_millis()
+
+local expires_at = tonumber(redis.call('ZSCORE', KEYS[1], ARGV[2]))
+if not expires_at then
+    return 0
+end
+redis.call('ZREM', KEYS[1], ARGV[2])
+
+-- a lease which has expired was already freed for others
+if expires_at <= now then
+    return 0
+end
+return 1
@@ -0,0 +1,16 @@
+-- KEYS[1]: leases held on the subject, scored by when they expire (millis)
+-- ARGV[2]: ID of the lease, ARGV[3]: new TTL of the lease (millis)
+local now = now_millis()
+local ttl = tonumber(ARGV[3])
+
+-- a lease which has expired may already have been taken by another holder
+local expires_at = tonumber(redis.call('ZSCORE', KEYS[1], ARGV[2]))
+if not expires_at or expires_at <= now then
+    return 0
+end
+redis.call('ZADD', KEYS[1], now + ttl, ARGV[2])
+
+local last = redis.call('ZRANGE', KEYS[1], -1, -1, 'WITHSCORES')
+redis.call('PEXPIRE', KEYS[1], tonumber(last[2]) - now)
+
+return 1
@@ -0,0 +1,87 @@
+-- the script of every algorithm is prepended as a function of its keys and arguments
+-- KEYS: the keys of every limit in turn, like given to the script of its algorithm
+-- ARGV[2]: 'record' the request on every limit if all allow it, 'peek' at the decisions it would get, or 'fetch' the current state of every limit
+-- ARGV[3]: number of limits, then per limit: its algorithm, the number of its keys, the number of its arguments,
+--          and the arguments of the script of its algorithm after the current time
+local algorithms = {
+    fixed_window = fixed_window,
+    sliding_log = sliding_log,
+    sliding_window = sliding_window,
+    leaky_bucket = leaky_bucket,
+    token_bucket = token_bucket,
+    gcra = gcra,
+}
+local mode = ARGV[2]
+
+-- the time is read once, so every limit decides at the same instant in the peek and the record
+local now = now_millis()
+now_millis = function()
+    return now
+end
+
+local limits = {}
+local next_key = 1
+local next_arg = 4
+for i = 1, tonumber(ARGV[3]) do
+    local algorithm = algorithms[ARGV[next_arg]]
+    local key_count = tonumber(ARGV[next_arg + 1])
+    local arg_count = tonumber(ARGV[next_arg + 2])
+    next_arg = next_arg + 3
+
+    local keys = {}
+    for j = 1, key_count do
+        keys[j] = KEYS[next_key]
+        next_key = next_key + 1
+    end
+    local args = {ARGV[1]}
+    for j = 1, arg_count do
+        args[j + 1] = ARGV[next_arg]
+        next_arg = next_arg + 1
+    end
+    limits[i] = {algorithm = algorithm, keys = keys, args = args}
+end
+
+-- every limit is peeked at first, so none of them is consumed unless all allow the request
+local decisions = {}
+local allowed = 1
+for i, limit in ipairs(limits) do
+    limit.args[4] = mode == 'fetch' and 'fetch' or 'peek'
+    decisions[i] = limit.algorithm(limit.keys, limit.args)
+    if decisions[i][1] == 0 then
+        allowed = 0
+    end
+end
+
+if allowed == 1 and mode == 'record' then
+    for i, limit in ipairs(limits) do
+        limit.args[4] = 'record'
+        decisions[i] = limit.algorithm(limit.keys, limit.args)
+        -- the limits are on distinct keys at the same instant, so the record decides like the peek
+        if decisions[i][1] == 0 then
+            return redis.error_reply('limit ' .. i .. ' denied the request it allowed on the peek')
+        end
+    end
+end
+
+-- the binding limit is the denying one to wait the longest for, or the allowing one with the
+-- fewest units left
+local binding = 1
+for i, decision in ipairs(decisions) do
+    local current = decisions[binding]
+    if allowed == 0 then
+        if decision[1] == 0 and (current[1] == 1 or decision[5] > current[5]) then
+            binding = i
+        end
+    elseif decision[3] < current[3] then
+        binding = i
+    end
+end
+
+local reply = {allowed, binding}
+for _, decision in ipairs(decisions) do
+    for _, value in ipairs(decision) do
+        reply[#reply + 1] = value
+    end
+end
+
+return reply
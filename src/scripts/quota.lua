@@ -0,0 +1,24 @@
+-- KEYS[1]: count of the calendar period
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the limit while they last
+-- ARGV[2]: end of the period (millis), ARGV[3]: limit, ARGV[4]: 'record' the request, 'peek' at the decision it would get, or 'fetch' the current state
+-- ARGV[5]: cost of the request (at most the limit), ARGV[6]: when the count expires (millis), a while after the end of the period
+local now = now_millis()
+local reset_at = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3]) + tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+local mode = ARGV[4]
+local cost = tonumber(ARGV[5])
+local expire_at = tonumber(ARGV[6])
+
+local count = tonumber(redis.call('GET', KEYS[1]) or '0')
+if count + cost > limit then
+    return {0, limit, math.max(limit - count, 0), reset_at, math.max(reset_at - now, 0)}
+end
+
+if mode == 'record' then
+    count = redis.call('INCRBY', KEYS[1], cost)
+    redis.call('PEXPIRE', KEYS[1], expire_at - now)
+elseif mode == 'peek' then
+    count = count + cost
+end
+
+return {1, limit, limit - count, reset_at, 0}
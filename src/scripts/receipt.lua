@@ -0,0 +1,12 @@
+-- the receipt of a request recorded to be refundable, unique for the subject: the time it was
+-- recorded at and its number in the sequence of the subject, which a refund of it is checked by
+local function receipt(sequence, now, ttl)
+    local seq = redis.call('INCR', sequence)
+    -- the sequence lives at least as long as the receipts it issued, so it never restarts below
+    -- the mark of a reset while a receipt from before the reset can still be refunded
+    if redis.call('PTTL', sequence) < ttl then
+        redis.call('PEXPIRE', sequence, ttl)
+    end
+    return now .. ':' .. seq
+end
+
@@ -0,0 +1,88 @@
+-- KEYS[1]: marker of the refund, KEYS[2]: sequence of the last receipt issued before the subject was reset
+-- KEYS[3..]: keys of the algorithm, like given to its script
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the capacity of the token bucket while they last
+-- ARGV[2]: period (millis), ARGV[3]: algorithm, ARGV[4]: cost of the request, ARGV[5]: limit, ARGV[6]: burst
+-- ARGV[7]: receipt of the request, '{recorded at (millis)}:{sequence}'
+local now = now_millis()
+local period = tonumber(ARGV[2])
+local algorithm = ARGV[3]
+local cost = tonumber(ARGV[4])
+local limit = tonumber(ARGV[5])
+local burst = tonumber(ARGV[6])
+local recorded_at, seq = string.match(ARGV[7], '^(%d+):(%d+)$')
+
+-- tolerance for the rounding of the refill and the leak, like in the scripts of the buckets
+local epsilon = 1e-9
+
+-- a request gives its units back for at most a period after it was recorded, only once, and
+-- not after the subject was reset, which cleared the units it consumed
+if now - tonumber(recorded_at) >= period then
+    return 0
+end
+if tonumber(seq) <= tonumber(redis.call('GET', KEYS[2]) or '0') then
+    return 0
+end
+if not redis.call('SET', KEYS[1], 1, 'NX', 'PX', period) then
+    return 0
+end
+
+if algorithm == 'fixed_window' or algorithm == 'sliding_window' then
+    -- the count of the window the request was recorded in, which may have expired meanwhile
+    local window = math.floor(tonumber(recorded_at) / period) * period
+    local key = KEYS[3] .. ':' .. window
+    local count = tonumber(redis.call('GET', key) or '0')
+    if count > 0 then
+        redis.call('DECRBY', key, math.min(cost, count))
+    end
+elseif algorithm == 'sliding_log' then
+    -- the members logged for the request, numbered up to its sequence
+    local members = {}
+    for i = tonumber(seq) - cost + 1, tonumber(seq) do
+        members[#members + 1] = recorded_at .. ':' .. i
+        if #members >= 1000 or i == tonumber(seq) then
+            redis.call('ZREM', KEYS[3], unpack(members))
+            members = {}
+        end
+    end
+elseif algorithm == 'leaky_bucket' then
+    local bucket = redis.call('HMGET', KEYS[3], 'level', 'last_leak')
+    local level = tonumber(bucket[1])
+    local last_leak = tonumber(bucket[2])
+    if level and last_leak then
+        level = math.max(level - math.max(now - last_leak, 0) * limit / period - cost, 0)
+        if level <= epsilon then
+            redis.call('DEL', KEYS[3])
+        else
+            redis.call('HSET', KEYS[3], 'level', tostring(level), 'last_leak', now)
+            redis.call('PEXPIRE', KEYS[3], math.ceil(level * period / limit - epsilon))
+        end
+    end
+elseif algorithm == 'token_bucket' then
+    local extra = tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+    local capacity = burst + extra
+    local last_refill = tonumber(redis.call('GET', KEYS[3]))
+    local tokens = tonumber(redis.call('GET', KEYS[4]))
+    if last_refill and tokens then
+        tokens = math.min(capacity, tokens + extra + math.max(now - last_refill, 0) * limit / period + cost)
+        -- a full bucket is the same as a missing one
+        if tokens + epsilon >= capacity then
+            redis.call('DEL', KEYS[3], KEYS[4])
+        else
+            local ttl = math.ceil((capacity - tokens) * period / limit - epsilon)
+            redis.call('SET', KEYS[3], now, 'PX', ttl)
+            redis.call('SET', KEYS[4], tostring(tokens - extra), 'PX', ttl)
+        end
+    end
+elseif algorithm == 'gcra' then
+    local tat = tonumber(redis.call('GET', KEYS[3]))
+    if tat then
+        tat = tat - period / limit * cost
+        if tat <= now + epsilon then
+            redis.call('DEL', KEYS[3])
+        else
+            redis.call('SET', KEYS[3], string.format('%.3f', tat), 'PX', math.ceil(tat - now))
+        end
+    end
+end
+
+return 1
@@ -0,0 +1,73 @@
+-- KEYS[1]: sorted set of the logged requests, KEYS[2]: sequence of the log members (and of the receipts)
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the limit while they last
+-- ARGV[2]: window size (millis), ARGV[3]: limit, ARGV[4]: 'record' the request, 'refundable' to record it with a receipt, 'peek' at the decision it would get, or 'fetch' the current state
+-- ARGV[5]: cost of the request (at most the limit), logged as that many members
+local now = now_millis()
+local size = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3]) + tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+local mode = ARGV[4]
+local cost = tonumber(ARGV[5])
+
+-- requests logged at or before this time have left the window
+local expired = now - size
+
+local count
+if mode == 'record' or mode == 'refundable' then
+    redis.call('ZREMRANGEBYSCORE', KEYS[1], 0, expired)
+    count = redis.call('ZCARD', KEYS[1])
+else
+    count = redis.call('ZCOUNT', KEYS[1], '(' .. expired, '+inf')
+end
+
+if count + cost > limit then
+    -- a request is allowed again once enough of the oldest requests have left the window
+    local oldest = redis.call('ZRANGEBYSCORE', KEYS[1], '(' .. expired, '+inf', 'WITHSCORES', 'LIMIT', count + cost - limit - 1, 1)
+    local newest = redis.call('ZRANGE', KEYS[1], -1, -1, 'WITHSCORES')
+    local retry_after = 0
+    if #oldest > 0 then
+        retry_after = tonumber(oldest[2]) + size - now
+    end
+    local reset_at = now
+    if #newest > 0 then
+        reset_at = tonumber(newest[2]) + size
+    end
+    return {0, limit, math.max(limit - count, 0), reset_at, retry_after}
+end
+
+if mode == 'fetch' then
+    local reset_at = now
+    if count > 0 then
+        local newest = redis.call('ZRANGE', KEYS[1], -1, -1, 'WITHSCORES')
+        reset_at = tonumber(newest[2]) + size
+    end
+    return {1, limit, limit - count, reset_at, 0}
+end
+
+if mode == 'peek' then
+    return {1, limit, limit - count - cost, now + size, 0}
+end
+
+-- requests logged in the same millisecond must not overwrite each other
+local seq = redis.call('INCRBY', KEYS[2], cost)
+local members = {}
+for i = 1, cost do
+    members[#members + 1] = now
+    members[#members + 1] = now .. ':' .. (seq - cost + i)
+    -- in batches, to stay below the limit of the arguments of a call
+    if #members >= 1000 or i == cost then
+        redis.call('ZADD', KEYS[1], unpack(members))
+        members = {}
+    end
+end
+redis.call('PEXPIRE', KEYS[1], size)
+-- the sequence is shared with the receipts, which may need it to live longer
+if redis.call('PTTL', KEYS[2]) < size then
+    redis.call('PEXPIRE', KEYS[2], size)
+end
+
+if mode == 'refundable' then
+    -- the members of the request are numbered up to the sequence
+    return {1, limit, limit - count - cost, now + size, 0, now .. ':' .. seq}
+end
+
+return {1, limit, limit - count - cost, now + size, 0}
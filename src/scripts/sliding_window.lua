@@ -0,0 +1,67 @@
+-- KEYS[1]: key of the subject, KEYS[2]: sequence of the receipts
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the limit while they last
+-- ARGV[2]: window size (millis), ARGV[3]: limit, ARGV[4]: 'record' the request, 'refundable' to record it with a receipt, 'peek' at the decision it would get, or 'fetch' the current state
+-- ARGV[5]: cost of the request (at most the limit)
+local now = now_millis()
+local size = tonumber(ARGV[2])
+local limit = tonumber(ARGV[3]) + tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+local mode = ARGV[4]
+local cost = tonumber(ARGV[5])
+
+local current_window = math.floor(now / size) * size
+local current_key = KEYS[1] .. ':' .. current_window
+local previous_key = KEYS[1] .. ':' .. (current_window - size)
+local window_end = current_window + size
+
+--  pre win  curr win
+-- |  size  |  size  |
+-- -------------------
+--      ^__size__^
+--      ^---^    ^---^
+--    section1 = section2 (weight1 = weight2)
+local weight = (window_end - now) / size
+local previous_count = tonumber(redis.call('GET', previous_key) or '0')
+local current_count = tonumber(redis.call('GET', current_key) or '0')
+local count = current_count + math.floor(previous_count * weight + 0.5)
+
+-- the requests of the previous window are weighted out at the end of the current window,
+-- and the requests of the current window at the end of the next one
+local function reset_at()
+    if current_count > 0 then
+        return window_end + size
+    end
+    if previous_count > 0 then
+        return window_end
+    end
+    return now
+end
+
+if count + cost > limit then
+    -- the weighted count leaves room for the cost once the weight w of the older window
+    -- satisfies older_count * w + newer_count < limit - cost + 0.5, i.e. strictly after retry_at
+    local target = limit - cost + 0.5
+    local retry_at
+    if current_count < target then
+        local w = (target - current_count) / previous_count
+        retry_at = window_end - w * size
+    else
+        local w = target / current_count
+        retry_at = window_end + size - w * size
+    end
+    return {0, limit, math.max(limit - count, 0), reset_at(), math.max(math.floor(retry_at - now) + 1, 0)}
+end
+
+if mode == 'record' or mode == 'refundable' then
+    current_count = redis.call('INCRBY', current_key, cost)
+    redis.call('PEXPIRE', current_key, size * 2)
+    count = count + cost
+elseif mode == 'peek' then
+    current_count = current_count + cost
+    count = count + cost
+end
+
+if mode == 'refundable' then
+    return {1, limit, limit - count, reset_at(), 0, receipt(KEYS[2], now, size)}
+end
+
+return {1, limit, limit - count, reset_at(), 0}
@@ -0,0 +1,3 @@
+-- KEYS[1]: key of the subject, so the time is read on the node which holds it (and runs its scripts)
+local time = redis.call('TIME')
+return {tonumber(time[1]), tonumber(time[2])}
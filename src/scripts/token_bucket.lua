@@ -0,0 +1,52 @@
+-- KEYS[1]: last refill time (millis), KEYS[2]: remaining tokens (fractional) less the granted ones
+-- KEYS[3]: sequence of the receipts
+-- KEYS[#KEYS]: extra units granted to the subject, which raise the capacity while they last
+-- ARGV[2]: refill period (millis), ARGV[3]: capacity (the burst of the rule), ARGV[4]: 'record' the request, 'refundable' to record it with a receipt, 'peek' at the decision it would get, or 'fetch' the current state
+-- ARGV[5]: cost of the request in tokens (at most the capacity), ARGV[6]: tokens refilled per period (the limit of the rule)
+local now = now_millis()
+local period = tonumber(ARGV[2])
+local extra = tonumber(redis.call('GET', KEYS[#KEYS]) or '0')
+local capacity = tonumber(ARGV[3]) + extra
+local mode = ARGV[4]
+local cost = tonumber(ARGV[5])
+local refill = tonumber(ARGV[6])
+
+-- tolerance for the rounding of the refill, so a request is allowed right at its retry time
+local epsilon = 1e-9
+
+-- the tokens accrue continuously since the last refill, up to the capacity
+local last_refill = tonumber(redis.call('GET', KEYS[1]))
+local tokens = tonumber(redis.call('GET', KEYS[2]))
+if not last_refill or not tokens then
+    tokens = capacity
+else
+    tokens = math.min(capacity, tokens + extra + math.max(now - last_refill, 0) * refill / period)
+end
+
+-- the time (millis from now) until the bucket holds the given tokens
+local function time_until(target)
+    if tokens + epsilon >= target then
+        return 0
+    end
+    return math.ceil((target - tokens) * period / refill - epsilon)
+end
+
+if tokens + epsilon < cost then
+    return {0, capacity, math.max(math.min(math.floor(tokens + epsilon), cost - 1), 0), now + time_until(capacity), time_until(cost)}
+end
+
+if mode ~= 'fetch' then
+    tokens = tokens - cost
+end
+if mode == 'record' or mode == 'refundable' then
+    -- a bucket left alone until it is full again is the same as a missing one
+    local ttl = time_until(capacity)
+    redis.call('SET', KEYS[1], now, 'PX', ttl)
+    redis.call('SET', KEYS[2], tostring(tokens - extra), 'PX', ttl)
+end
+
+if mode == 'refundable' then
+    return {1, capacity, math.floor(tokens + epsilon), now + time_until(capacity), 0, receipt(KEYS[3], now, period)}
+end
+
+return {1, capacity, math.floor(tokens + epsilon), now + time_until(capacity), 0}
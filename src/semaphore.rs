@@ -0,0 +1,66 @@
+use crate::decision::Lease;
+use crate::error::RateLimitError;
+use crate::rate_limiter_redis::{subject_key, RateLimiterRedis};
+use redis::ConnectionLike;
+use std::time::Duration;
+
+/// A distributed semaphore, which caps the work in flight for a subject (e.g. at most 5
+/// concurrent report generations per tenant) rather than the rate of its requests. Every slot
+/// is held by a lease with a TTL, so the slots of crashed holders are freed once it expires.
+impl<C: ConnectionLike> RateLimiterRedis<C> {
+    /// Acquires one of `limit` leases of the subject on the resource, held for `ttl` unless it
+    /// is released or renewed before. Returns `None` when `limit` leases are already held.
+    pub fn acquire_lease(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        limit: u64,
+        ttl: Duration,
+    ) -> Result<Option<Lease>, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.scripts
+            .lease_acquire(&key, now, limit, ttl)?
+            .invoke(&mut self.conn)
+            .map_err(RateLimitError::from_script)
+    }
+
+    /// Frees the slot of the lease. Returns false when the lease was already released or has
+    /// expired, since its slot may then be held by another lease.
+    pub fn release_lease(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        id: &str,
+    ) -> Result<bool, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.scripts
+            .lease_release(&key, now, id)
+            .invoke(&mut self.conn)
+            .map_err(RateLimitError::from_script)
+    }
+
+    /// Extends the lease to `ttl` from now, e.g. as a heartbeat of work which lasts longer than
+    /// the TTL it was acquired with. Returns false when the lease was released or has expired.
+    pub fn renew_lease(
+        &mut self,
+        key_prefix: &str,
+        resource: &str,
+        subject: &str,
+        id: &str,
+        ttl: Duration,
+    ) -> Result<bool, RateLimitError> {
+        let key = subject_key(key_prefix, resource, subject);
+        let now = self.time_source.script_arg(&*self.clock)?;
+
+        self.scripts
+            .lease_renew(&key, now, id, ttl)?
+            .invoke(&mut self.conn)
+            .map_err(RateLimitError::from_script)
+    }
+}
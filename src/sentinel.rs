@@ -0,0 +1,245 @@
+use redis::{
+    Client, Connection, ConnectionAddr, ConnectionInfo, ConnectionLike, ErrorKind, FromRedisValue,
+    RedisConnectionInfo, RedisError, RedisResult, Value,
+};
+use std::collections::HashMap;
+use std::io;
+
+/// How the rate limiter connects to the Redis found through Sentinel.
+#[derive(Debug, Clone, Default)]
+pub struct SentinelConfig {
+    /// Sends the read-only `fetch_*` calls to a replica instead of the master. The replicas are
+    /// updated asynchronously, so a fetch may not see the latest recorded requests yet.
+    pub read_from_replicas: bool,
+    /// The database, username and password of the master and the replicas. The ones of the
+    /// sentinels are given by their URLs.
+    pub redis: RedisConnectionInfo,
+}
+
+/// The role of the node a [`SentinelConnection`] is connected to, as reported by ROLE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Master,
+    Replica,
+}
+
+impl Role {
+    fn name(self) -> &'static str {
+        match self {
+            Role::Master => "master",
+            Role::Replica => "slave",
+        }
+    }
+}
+
+/// A connection to the master, or to a replica, of a Redis monitored by Sentinel.
+///
+/// The node is discovered by asking the sentinels in order. When the connection fails, or the
+/// master turns out to be a replica after a failover (a READONLY reply), the node is discovered
+/// again and the command is sent once more. A command lost with a dropped connection may
+/// therefore run twice.
+pub struct SentinelConnection {
+    sentinels: Vec<Client>,
+    master_name: String,
+    redis: RedisConnectionInfo,
+    role: Role,
+    conn: Connection,
+}
+
+impl SentinelConnection {
+    /// Connects to the current master.
+    pub(crate) fn master(
+        sentinels: &[&str],
+        master_name: &str,
+        redis: &RedisConnectionInfo,
+    ) -> RedisResult<Self> {
+        SentinelConnection::connect(sentinels, master_name, redis, Role::Master)
+    }
+
+    /// Connects to a healthy replica, or to the master if there is none.
+    pub(crate) fn replica(
+        sentinels: &[&str],
+        master_name: &str,
+        redis: &RedisConnectionInfo,
+    ) -> RedisResult<Self> {
+        SentinelConnection::connect(sentinels, master_name, redis, Role::Replica)
+    }
+
+    fn connect(
+        sentinels: &[&str],
+        master_name: &str,
+        redis: &RedisConnectionInfo,
+        role: Role,
+    ) -> RedisResult<Self> {
+        let sentinels = sentinels
+            .iter()
+            .map(|sentinel| Client::open(*sentinel))
+            .collect::<RedisResult<Vec<_>>>()?;
+        let conn = discover(&sentinels, master_name, redis, role)?;
+
+        Ok(SentinelConnection {
+            sentinels,
+            master_name: master_name.to_string(),
+            redis: redis.clone(),
+            role,
+            conn,
+        })
+    }
+
+    /// Runs the request, and runs it again on the node discovered anew if it failed because
+    /// the node is gone or has failed over.
+    fn retry<T>(&mut self, request: impl Fn(&mut Connection) -> RedisResult<T>) -> RedisResult<T> {
+        match request(&mut self.conn) {
+            Err(err) if is_failed_over(&err) => {
+                self.conn = discover(&self.sentinels, &self.master_name, &self.redis, self.role)?;
+                request(&mut self.conn)
+            }
+            result => result,
+        }
+    }
+}
+
+impl ConnectionLike for SentinelConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.retry(|conn| conn.req_packed_command(cmd))
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.retry(|conn| conn.req_packed_commands(cmd, offset, count))
+    }
+
+    fn get_db(&self) -> i64 {
+        self.conn.get_db()
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.conn.check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        self.conn.is_open()
+    }
+}
+
+/// Whether the error tells the node is gone, or is not a master anymore. Before Redis 7, a
+/// write of a script on a replica fails with a script error holding the READONLY reply.
+fn is_failed_over(err: &RedisError) -> bool {
+    err.is_io_error()
+        || err.kind() == ErrorKind::ReadOnly
+        || err
+            .detail()
+            .is_some_and(|detail| detail.contains("READONLY"))
+}
+
+/// Asks the sentinels in order for the node holding the role, and connects to the first one
+/// found. Returns the last error if no sentinel could be asked.
+fn discover(
+    sentinels: &[Client],
+    master_name: &str,
+    redis: &RedisConnectionInfo,
+    role: Role,
+) -> RedisResult<Connection> {
+    let mut last_err = None;
+    for sentinel in sentinels {
+        match ask(sentinel, master_name, redis, role) {
+            Ok(Some(conn)) => return Ok(conn),
+            Ok(None) => {}
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    Err(last_err
+        .unwrap_or_else(|| unavailable(format!("no sentinel knows the master {master_name}"))))
+}
+
+/// Asks one sentinel for the node holding the role. A replica falls back to the master when
+/// none of the replicas is healthy or can be connected to.
+fn ask(
+    sentinel: &Client,
+    master_name: &str,
+    redis: &RedisConnectionInfo,
+    role: Role,
+) -> RedisResult<Option<Connection>> {
+    let mut conn = sentinel.get_connection()?;
+
+    if role == Role::Replica {
+        let replicas: Vec<HashMap<String, String>> = redis::cmd("SENTINEL")
+            .arg("REPLICAS")
+            .arg(master_name)
+            .query(&mut conn)?;
+        for replica in replicas.iter().filter(|replica| is_healthy(replica)) {
+            let (Some(host), Some(Ok(port))) = (
+                replica.get("ip"),
+                replica.get("port").map(|port| port.parse()),
+            ) else {
+                continue;
+            };
+            if let Ok(conn) = connect(host, port, redis, Role::Replica) {
+                return Ok(Some(conn));
+            }
+        }
+    }
+
+    let master: Option<(String, u16)> = redis::cmd("SENTINEL")
+        .arg("GET-MASTER-ADDR-BY-NAME")
+        .arg(master_name)
+        .query(&mut conn)?;
+    match master {
+        Some((host, port)) => connect(&host, port, redis, Role::Master).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Whether the sentinel sees the replica as up and connected to its master.
+fn is_healthy(replica: &HashMap<String, String>) -> bool {
+    let flags = replica.get("flags").map(String::as_str).unwrap_or_default();
+    let link = replica
+        .get("master-link-status")
+        .map(String::as_str)
+        .unwrap_or("ok");
+
+    !flags
+        .split(',')
+        .any(|flag| matches!(flag, "s_down" | "o_down" | "disconnected"))
+        && link == "ok"
+}
+
+/// Connects to the node, and checks it holds the role the sentinel reported, since a sentinel
+/// may answer with the old master before it notices a failover.
+fn connect(
+    host: &str,
+    port: u16,
+    redis: &RedisConnectionInfo,
+    role: Role,
+) -> RedisResult<Connection> {
+    let info = ConnectionInfo {
+        addr: ConnectionAddr::Tcp(host.to_string(), port),
+        redis: redis.clone(),
+    };
+    let mut conn = Client::open(info)?.get_connection()?;
+
+    let reply: Vec<Value> = redis::cmd("ROLE").query(&mut conn)?;
+    let actual = reply
+        .first()
+        .map(String::from_redis_value)
+        .transpose()?
+        .unwrap_or_default();
+    if actual != role.name() {
+        return Err(unavailable(format!(
+            "{host}:{port} is a {actual}, not a {}",
+            role.name()
+        )));
+    }
+
+    Ok(conn)
+}
+
+/// An error for a node which can not be reached, classified as a connection error.
+fn unavailable(reason: String) -> RedisError {
+    RedisError::from(io::Error::new(io::ErrorKind::NotConnected, reason))
+}
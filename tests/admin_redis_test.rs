@@ -0,0 +1,266 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<()> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter::Algorithm;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+
+    /// Tests the subject is reset across the algorithms and windows, and only that subject.
+    #[test]
+    fn admin_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        // the fixed and the sliding window share the key of the window
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(MockClock::new(Duration::from_secs(1_700_000_000)));
+        let key_prefix = "test15";
+        let resource = "data";
+        for subject in ["andy", "bob"] {
+            client.record_fixed_window(key_prefix, resource, subject, &rule)?;
+            client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+            client.record_sliding_window(key_prefix, resource, subject, &rule)?;
+            client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+            client.record_gcra(key_prefix, resource, subject, &rule)?;
+        }
+        client.record_fixed_window(
+            key_prefix,
+            resource,
+            "andy",
+            &Rule::new(1, Duration::from_secs(1)),
+        )?;
+
+        // act
+        let actual = client.reset_subject(key_prefix, resource, "andy")?;
+
+        // assert, the sequence of the receipts is kept
+        assert_eq!(actual, 6);
+
+        let actual = client.record_fixed_window(key_prefix, resource, "andy", &rule)?;
+        assert!(actual.allowed);
+        let actual = client.record_gcra(key_prefix, resource, "andy", &rule)?;
+        assert!(actual.allowed);
+
+        let actual = client.record_fixed_window(key_prefix, resource, "bob", &rule)?;
+        assert!(!actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests every subject of the resource is reset, and only that resource.
+    #[test]
+    fn admin_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test15";
+        for (resource, subject) in [("data", "andy"), ("data", "bob"), ("data:v2", "andy")] {
+            client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        }
+
+        // act
+        let actual = client.reset_resource(key_prefix, "data")?;
+
+        // assert, the logs of both subjects, their sequences of the receipts are kept
+        assert_eq!(actual, 2);
+
+        let actual = client.record_sliding_log(key_prefix, "data", "bob", &rule)?;
+        assert!(actual.allowed);
+
+        let actual = client.record_sliding_log(key_prefix, "data:v2", "andy", &rule)?;
+        assert!(!actual.allowed);
+
+        Ok(())
+    }
+
+    /// Integration: Throttled -> Granted -> Allowed -> Expired -> Throttled
+    #[test]
+    fn admin_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(2, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test15";
+        let resource = "data";
+        let subject = "andy";
+        client.record_fixed_window_weighted(key_prefix, resource, subject, &rule, 2)?;
+        client.record_token_bucket_weighted(key_prefix, resource, subject, &rule, 2)?;
+
+        // act
+        client.grant_extra_quota(key_prefix, resource, subject, 3, Duration::from_millis(500))?;
+
+        // assert
+        let actual = client.record_fixed_window(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.limit, 5);
+        assert_eq!(actual.remaining, 2);
+
+        let actual =
+            client.record_token_bucket_weighted(key_prefix, resource, subject, &rule, 2)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 1);
+
+        let actual = client.peek_gcra(key_prefix, resource, subject, &rule, 2)?;
+        assert_eq!(actual.limit, 5);
+        assert_eq!(actual.remaining, 3);
+
+        // more requests, not larger ones: the cost is still capped by the rule
+        let actual = client.record_fixed_window_weighted(key_prefix, resource, subject, &rule, 3);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        // the grant is not reset with the subject
+        client.reset_subject(key_prefix, resource, subject)?;
+        let actual = client.fetch_sliding_window(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 5);
+
+        // expired
+        std::thread::sleep(Duration::from_millis(600));
+        client.record_fixed_window_weighted(key_prefix, resource, subject, &rule, 2)?;
+        let actual = client.record_fixed_window(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.limit, 2);
+
+        Ok(())
+    }
+
+    /// Tests the keys under a prefix are listed, with the characters of a glob matched literally.
+    #[test]
+    fn admin_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        for (key_prefix, subject) in [("test15", "a*"), ("test15", "ab"), ("test15x", "a*")] {
+            client.record_leaky_bucket(key_prefix, "data", subject, &rule)?;
+        }
+
+        // act && assert
+        let actual = client.active_keys("test15")?;
+        assert_eq!(actual, vec!["test15:data:{a*}", "test15:data:{ab}"]);
+
+        let actual = client.reset_subject("test15", "data", "a*")?;
+        assert_eq!(actual, 1);
+
+        let actual = client.active_keys("test15")?;
+        assert_eq!(actual, vec!["test15:data:{ab}"]);
+
+        Ok(())
+    }
+
+    /// Tests the error when the grant would expire right away.
+    #[test]
+    fn admin_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+
+        // act
+        let actual = client.grant_extra_quota("test15", "data", "andy", 1, Duration::ZERO);
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        Ok(())
+    }
+
+    /// Tests a request refunded before the subject is reset can not be refunded again after it.
+    #[test]
+    fn admin_redis_case6() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(2, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(MockClock::new(Duration::from_secs(1_700_000_000)));
+        let (_, token) =
+            client.record_refundable(Algorithm::FixedWindow, "test15", "data", "andy", &rule, 1)?;
+        let token = token.expect("the request should be refundable");
+        assert!(client.refund(&token)?);
+
+        // act
+        client.reset_subject("test15", "data", "andy")?;
+
+        // assert
+        client.record_fixed_window_weighted("test15", "data", "andy", &rule, 2)?;
+        assert!(!client.refund(&token)?);
+
+        let actual = client.fetch_fixed_window("test15", "data", "andy", &rule)?;
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests a request recorded before the subject is reset is not refunded after it, so the
+    /// requests recorded since keep the units they consumed.
+    #[test]
+    fn admin_redis_case7() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(5, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(MockClock::new(Duration::from_secs(1_700_000_000)));
+        let (_, before) =
+            client.record_refundable(Algorithm::FixedWindow, "test15", "data", "andy", &rule, 5)?;
+        let before = before.expect("the request should be refundable");
+
+        // act
+        client.reset_subject("test15", "data", "andy")?;
+        let (_, after) =
+            client.record_refundable(Algorithm::FixedWindow, "test15", "data", "andy", &rule, 5)?;
+        let after = after.expect("the request should be refundable");
+
+        // assert
+        assert!(!client.refund(&before)?);
+        let actual = client.fetch_fixed_window("test15", "data", "andy", &rule)?;
+        assert_eq!(actual.remaining, 0);
+
+        // the requests recorded since are refunded
+        assert!(client.refund(&after)?);
+        let actual = client.fetch_fixed_window("test15", "data", "andy", &rule)?;
+        assert_eq!(actual.remaining, 5);
+
+        // nor is a request recorded before the whole resource is reset
+        let (_, before) =
+            client.record_refundable(Algorithm::FixedWindow, "test15", "data", "bob", &rule, 5)?;
+        let before = before.expect("the request should be refundable");
+        client.reset_resource("test15", "data")?;
+        client.record_fixed_window_weighted("test15", "data", "bob", &rule, 5)?;
+        assert!(!client.refund(&before)?);
+
+        Ok(())
+    }
+}
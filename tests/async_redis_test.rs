@@ -0,0 +1,143 @@
+// NOTE: cargo test --all --all-features -- --test-threads 1
+#![cfg(feature = "async")]
+
+fn initialize_redis() -> redis::RedisResult<()> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis::TimeSource;
+    use rrr::rate_limiter_redis_async::RateLimiterRedisAsync;
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+    const TASKS: usize = 8;
+    const REQUESTS_PER_TASK: usize = 50;
+
+    /// Integration: Initiated -> Throttled -> Reset -> Refilled
+    #[tokio::test]
+    async fn async_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(1));
+        let client = RateLimiterRedisAsync::open(CONN).await?;
+        let key_prefix = "test10";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let actual = client
+            .record_sliding_window(key_prefix, resource, subject, &rule)
+            .await?;
+        assert!(actual.allowed);
+
+        // throttled
+        let actual = client
+            .record_sliding_window(key_prefix, resource, subject, &rule)
+            .await?;
+        assert!(!actual.allowed);
+
+        // reset
+        client
+            .reset_sliding_window(key_prefix, resource, subject, &rule)
+            .await?;
+
+        let actual = client
+            .fetch_sliding_window(key_prefix, resource, subject, &rule)
+            .await?;
+        assert_eq!(actual.remaining, 1);
+
+        // refilled
+        let actual = client
+            .record_sliding_window(key_prefix, resource, subject, &rule)
+            .await?;
+        assert!(actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests one limiter shared by many tasks never exceeds the limit.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn async_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(100, Duration::from_secs(100));
+        let mut client = RateLimiterRedisAsync::open(CONN).await?;
+        // no window rolls over while the tasks run
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(MockClock::new(Duration::from_secs(1_700_000_000)));
+        let client = Arc::new(client);
+
+        // act
+        let mut tasks = Vec::with_capacity(TASKS);
+        for _ in 0..TASKS {
+            let client = client.clone();
+            tasks.push(tokio::spawn(async move {
+                let mut allowed = 0;
+                for _ in 0..REQUESTS_PER_TASK {
+                    if client
+                        .record_fixed_window("test10", "data", "andy", &rule)
+                        .await?
+                        .allowed
+                    {
+                        allowed += 1;
+                    }
+                }
+                Ok::<usize, RateLimitError>(allowed)
+            }));
+        }
+
+        let mut allowed = 0;
+        for task in tasks {
+            allowed += task.await.expect("the task should not panic")?;
+        }
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        Ok(())
+    }
+
+    /// Tests waiting for the slots of the leaky bucket spaces the requests by the rate of the rule.
+    #[tokio::test]
+    async fn async_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(5, Duration::from_secs(1)).with_burst(1);
+        let max_delay = Duration::from_secs(10);
+        let client = RateLimiterRedisAsync::open(CONN).await?;
+
+        // act
+        let start = Instant::now();
+        for _ in 0..4 {
+            let actual = client
+                .wait_leaky_bucket("test10", "data", "andy", &rule, max_delay)
+                .await?;
+            assert!(actual.scheduled);
+        }
+
+        // assert, the first request is processed at once and the others every 200ms
+        assert!(start.elapsed() >= Duration::from_millis(590));
+        assert!(start.elapsed() < Duration::from_millis(900));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+// NOTE: docker-compose --profile cluster up -d && cargo test --all --all-features -- --test-threads 1
+#![cfg(feature = "cluster")]
+
+const NODES: [&str; 6] = [
+    "redis://127.0.0.1:7000/",
+    "redis://127.0.0.1:7001/",
+    "redis://127.0.0.1:7002/",
+    "redis://127.0.0.1:7003/",
+    "redis://127.0.0.1:7004/",
+    "redis://127.0.0.1:7005/",
+];
+
+fn initialize_redis() -> redis::RedisResult<redis::cluster::ClusterConnection> {
+    let client = redis::cluster::ClusterClient::new(NODES.to_vec())?;
+    let mut conn = client.get_connection()?;
+
+    redis::cmd("FLUSHALL").query::<()>(&mut conn)?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter::Algorithm;
+    use rrr::rate_limiter_redis::RateLimiterRedisCluster;
+    use rrr::rule::Rule;
+    use std::time::Duration;
+
+    const ALGORITHMS: [&str; 6] = [
+        "fixed_window",
+        "sliding_log",
+        "sliding_window",
+        "leaky_bucket",
+        "token_bucket",
+        "gcra",
+    ];
+
+    /// Integration: Initiated -> Throttled -> Reset -> Refilled, for every algorithm on the cluster.
+    #[test]
+    fn cluster_redis_case1() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // prev
+            initialize_redis()?;
+
+            // arrange
+            let rule = Rule::new(1, Duration::from_secs(1));
+            let client = RateLimiterRedisCluster::open_cluster(&NODES)?;
+            let algorithm: Algorithm = name.parse()?;
+            let mut limiter = algorithm.build(client, "test12", rule);
+            let resource = "data";
+            let subject = "andy";
+
+            // act && assert
+            let actual = limiter.check(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+
+            // throttled
+            let actual = limiter.check(resource, subject)?;
+            assert!(!actual.allowed, "{name}");
+
+            // reset
+            limiter.reset(resource, subject)?;
+
+            let actual = limiter.fetch(resource, subject)?;
+            assert_eq!(actual.remaining, 1, "{name}");
+
+            // refilled
+            let actual = limiter.check(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+        }
+
+        Ok(())
+    }
+
+    /// Tests all keys of a subject share a slot, whatever the algorithm.
+    #[test]
+    fn cluster_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        let mut conn = initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(10));
+        let mut client = RateLimiterRedisCluster::open_cluster(&NODES)?;
+        let key_prefix = "test12";
+        let resource = "data";
+        let subject = "andy";
+
+        // act
+        client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        client.record_sliding_window(key_prefix, resource, subject, &rule)?;
+        client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        client.record_gcra(key_prefix, resource, subject, &rule)?;
+
+        // assert
+        let subject_slot: u16 = redis::cmd("CLUSTER")
+            .arg("KEYSLOT")
+            .arg(subject)
+            .query(&mut conn)?;
+        for key in [
+            "test12:data:{andy}",
+            "test12:data:{andy}:seq",
+            "test12:data:{andy}:last_set_time",
+            "test12:data:{andy}:remain_requests",
+            "test12:data:{andy}:tat",
+        ] {
+            let actual: u16 = redis::cmd("CLUSTER")
+                .arg("KEYSLOT")
+                .arg(key)
+                .query(&mut conn)?;
+            assert_eq!(actual, subject_slot, "{key}");
+        }
+
+        Ok(())
+    }
+}
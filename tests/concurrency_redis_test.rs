@@ -0,0 +1,169 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<()> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis::{RateLimiterRedis, TimeSource};
+    use rrr::{decision::Decision, rule::Rule};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+    const THREADS: usize = 8;
+    const REQUESTS_PER_THREAD: usize = 50;
+
+    type Record =
+        fn(&mut RateLimiterRedis, &str, &str, &str, &Rule) -> Result<Decision, RateLimitError>;
+
+    /// Sends requests for the same key from many threads at once and returns how many of them were allowed.
+    /// The threads share a mock clock, so no window rolls over and no bucket refills meanwhile.
+    fn hammer(record: Record, rule: Rule) -> Result<usize, RateLimitError> {
+        let barrier = Arc::new(std::sync::Barrier::new(THREADS));
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut workers = Vec::with_capacity(THREADS);
+        for _ in 0..THREADS {
+            let mut client = RateLimiterRedis::open(CONN)?;
+            client.time_source = TimeSource::Client;
+            client.clock = Arc::new(clock.clone());
+            let barrier = barrier.clone();
+            workers.push(std::thread::spawn(
+                move || -> Result<usize, RateLimitError> {
+                    barrier.wait();
+                    let mut allowed = 0;
+                    for _ in 0..REQUESTS_PER_THREAD {
+                        if record(&mut client, "test6", "data", "andy", &rule)?.allowed {
+                            allowed += 1;
+                        }
+                    }
+                    Ok(allowed)
+                },
+            ));
+        }
+
+        let mut allowed = 0;
+        for worker in workers {
+            allowed += worker.join().expect("the worker should not panic")?;
+        }
+
+        Ok(allowed)
+    }
+
+    /// Tests the concurrent requests never exceed the limit in fixed window.
+    #[test]
+    fn fixed_window_concurrency() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(100, Duration::from_secs(100));
+
+        // act
+        let allowed = hammer(RateLimiterRedis::record_fixed_window, rule)?;
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        Ok(())
+    }
+
+    /// Tests the concurrent requests never exceed the limit in sliding log.
+    #[test]
+    fn sliding_log_concurrency() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(100, Duration::from_secs(100));
+
+        // act
+        let allowed = hammer(RateLimiterRedis::record_sliding_log, rule)?;
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        Ok(())
+    }
+
+    /// Tests the concurrent requests never exceed the limit in sliding window.
+    #[test]
+    fn sliding_window_concurrency() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(100, Duration::from_secs(100));
+
+        // act
+        let allowed = hammer(RateLimiterRedis::record_sliding_window, rule)?;
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        Ok(())
+    }
+
+    /// Tests the concurrent requests never exceed the limit in leaky bucket.
+    #[test]
+    fn leaky_bucket_concurrency() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(3600)).with_burst(100);
+
+        // act
+        let allowed = hammer(RateLimiterRedis::record_leaky_bucket, rule)?;
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        Ok(())
+    }
+
+    /// Tests the concurrent requests never exceed the limit in token bucket.
+    #[test]
+    fn token_bucket_concurrency() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(3600)).with_burst(100);
+
+        // act
+        let allowed = hammer(RateLimiterRedis::record_token_bucket, rule)?;
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        Ok(())
+    }
+
+    /// Tests the concurrent requests never exceed the limit in GCRA.
+    #[test]
+    fn gcra_concurrency() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(3600)).with_burst(100);
+
+        // act
+        let allowed = hammer(RateLimiterRedis::record_gcra, rule)?;
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        Ok(())
+    }
+}
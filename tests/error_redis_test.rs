@@ -0,0 +1,136 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<redis::Connection> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis;
+    use rrr::rule::Rule;
+    use std::time::Duration;
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+
+    /// Tests the error when the Redis can not be reached.
+    #[test]
+    fn error_redis_case1() {
+        // act
+        let actual = rate_limiter_redis::RateLimiterRedis::open("redis://127.0.0.1:1/");
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::Connection(_))));
+    }
+
+    /// Tests the error when the address of the Redis is malformed.
+    #[test]
+    fn error_redis_case2() {
+        // act
+        let actual = rate_limiter_redis::RateLimiterRedis::open("localhost:6379");
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+    }
+
+    /// Tests the error when the period of the rule is too small.
+    #[test]
+    fn error_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+
+        // act
+        let actual =
+            client.record_fixed_window("test7", "data", "andy", &Rule::new(1, Duration::ZERO));
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        Ok(())
+    }
+
+    /// Tests the error when the key of the subject holds another type.
+    #[test]
+    fn error_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        let mut conn = initialize_redis()?;
+        let _: () = redis::cmd("SET")
+            .arg("test7:data:{andy}")
+            .arg("not a queue")
+            .query(&mut conn)?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+
+        // act
+        let actual = client.record_leaky_bucket(
+            "test7",
+            "data",
+            "andy",
+            &Rule::new(1, Duration::from_secs(1)),
+        );
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::WrongType(_))));
+
+        Ok(())
+    }
+
+    /// Tests the error when the period of the rule is below the millisecond precision.
+    #[test]
+    fn error_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+
+        // act
+        let actual = client.record_sliding_log(
+            "test7",
+            "data",
+            "andy",
+            &Rule::new(1, Duration::from_micros(500)),
+        );
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        Ok(())
+    }
+
+    /// Tests the error when the cost of a request is zero, or could never be allowed.
+    #[test]
+    fn error_redis_case6() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(1)).with_burst(3);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+
+        // act && assert
+        let actual = client.record_fixed_window_weighted("test7", "data", "andy", &rule, 0);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        let actual = client.record_fixed_window_weighted("test7", "data", "andy", &rule, 2);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        let actual = client.record_token_bucket_weighted("test7", "data", "andy", &rule, 3)?;
+        assert!(actual.allowed);
+
+        let actual = client.record_token_bucket_weighted("test7", "data", "andy", &rule, 4);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        Ok(())
+    }
+}
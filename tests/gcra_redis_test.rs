@@ -0,0 +1,192 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<redis::Connection> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+
+    /// Tests the requests exceed the rate limit.
+    #[test]
+    fn gcra_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let limit_count = 1;
+        let size = Duration::from_secs(1);
+        let rule = Rule::new(limit_count, size);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test9";
+        let resource = "data";
+        let subject = "andy";
+
+        // act
+        let actual = client.record_gcra(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        let actual = client.record_gcra(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+
+        Ok(())
+    }
+
+    /// Integration: Initiated -> Throttled -> Retry After -> Allowed, with the exact retry after.
+    #[test]
+    fn gcra_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(3, Duration::from_millis(300));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test9";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        for remaining in (0..3).rev() {
+            let actual = client.record_gcra(key_prefix, resource, subject, &rule)?;
+            assert!(actual.allowed);
+            assert_eq!(actual.limit, 3);
+            assert_eq!(actual.remaining, remaining);
+        }
+
+        // throttled, one request is emitted every 100ms
+        let actual = client.record_gcra(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_millis(100));
+        assert_eq!(
+            actual.reset_at,
+            SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_300)
+        );
+
+        // retry after
+        clock.advance(actual.retry_after);
+
+        let actual = client.record_gcra(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests the burst is let through at once, and then the requests are spaced by the rate.
+    #[test]
+    fn gcra_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(1)).with_burst(5);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test9";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        for _ in 0..5 {
+            let actual = client.record_gcra(key_prefix, resource, subject, &rule)?;
+            assert!(actual.allowed);
+        }
+
+        let actual = client.record_gcra(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+
+        // one request accrues every 100ms
+        clock.advance(Duration::from_millis(250));
+
+        let actual = client.fetch_gcra(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 2);
+
+        // reset
+        client.reset_gcra(key_prefix, resource, subject)?;
+
+        let actual = client.fetch_gcra(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 5);
+
+        Ok(())
+    }
+
+    /// Tests only one key is stored per subject.
+    #[test]
+    fn gcra_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        let mut conn = initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(100, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test9";
+        let resource = "data";
+
+        // act
+        for subject in ["andy", "bob", "carol"] {
+            for _ in 0..10 {
+                client.record_gcra(key_prefix, resource, subject, &rule)?;
+            }
+        }
+
+        // assert
+        let actual: u64 = redis::cmd("DBSIZE").query(&mut conn)?;
+        assert_eq!(actual, 3);
+
+        Ok(())
+    }
+
+    /// Tests the weighted requests wait until the bucket has room for their cost, with a mock clock.
+    #[test]
+    fn gcra_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(1));
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test9";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let actual = client.record_gcra_weighted(key_prefix, resource, subject, &rule, 6)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 4);
+
+        // less than the cost remains
+        let actual = client.record_gcra_weighted(key_prefix, resource, subject, &rule, 5)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.remaining, 4);
+        assert_eq!(actual.retry_after, Duration::from_millis(100));
+
+        clock.advance(actual.retry_after);
+        let actual = client.record_gcra_weighted(key_prefix, resource, subject, &rule, 5)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+}
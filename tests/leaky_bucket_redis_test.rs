@@ -1,17 +1,10 @@
 // NOTE: cargo test --all -- --test-threads 1
-fn initialize_redis() -> Result<(), ()> {
+fn initialize_redis() -> redis::RedisResult<()> {
     let redis_address: &str = "redis://127.0.0.1:6379/";
-    let client = redis::Client::open(redis_address).map_err(|err| {
-        eprintln!("Error: could not open the connection to the Redis({redis_address}): {err}")
-    })?;
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
 
-    let mut conn = client.get_connection().map_err(|err| {
-        eprintln!("Error: client could not get the connection to the Redis: {err}")
-    })?;
-
-    let _: String = redis::cmd("FLUSHALL")
-        .query(&mut conn)
-        .map_err(|err| eprintln!("Error: could not flush all data in Redis: {err}"))?;
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
 
     Ok(())
 }
@@ -19,66 +12,287 @@ fn initialize_redis() -> Result<(), ()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rrr::rate_limiter_redis;
-    use std::{sync::mpsc::TryRecvError, time::Duration};
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
 
     const CONN: &str = "redis://127.0.0.1:6379/";
 
     /// Integration: Initiated -> Throttled -> Cool Down -> Refilled
     #[test]
-    fn leaky_bucket_redis_case1() -> Result<(), ()> {
+    fn leaky_bucket_redis_case1() -> Result<(), RateLimitError> {
         // prev
         initialize_redis()?;
 
         // arrange
         let limit_count = 1;
         let size = Duration::from_secs(1);
-        let mut client_consumer = rate_limiter_redis::RateLimiterRedis::open(CONN, limit_count)?;
-        let mut client_requester = rate_limiter_redis::RateLimiterRedis::open(CONN, limit_count)?;
+        let rule = Rule::new(limit_count, size);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
         let key_prefix = "test4";
         let resource = "data";
         let subject = "andy";
 
-        let (tx, rx) = std::sync::mpsc::channel::<()>();
-        let consumer = std::thread::spawn(move || -> Result<(), ()> {
-            loop {
-                println!("Consuming...");
-                client_consumer.consume_leaky_bucket(key_prefix, resource, subject, size)?;
-                std::thread::sleep(Duration::from_secs(size.as_secs()));
-                match rx.try_recv() {
-                    Ok(_) | Err(TryRecvError::Disconnected) => {
-                        println!("The consumer is terminating...");
-                        break;
-                    }
-                    Err(TryRecvError::Empty) => {}
-                }
-            }
-            Ok(())
-        });
-
         // act && assert
-        let actual = client_requester.record_leaky_bucket(key_prefix, resource, subject, size)?;
-        assert!(actual);
+        let actual = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
 
         // throttled
-        let actual = client_requester.record_leaky_bucket(key_prefix, resource, subject, size)?;
-        assert!(!actual);
+        let actual = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
 
         // cool down
-        std::thread::sleep(Duration::from_secs(1));
-        let count = client_requester.fetch_leaky_bucket(key_prefix, resource, subject, size)?;
-        assert_eq!(count, 0);
+        clock.advance(Duration::from_secs(1));
+        let actual = client.fetch_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 1);
 
         // refilled
-        let actual = client_requester.record_leaky_bucket(key_prefix, resource, subject, size)?;
-        assert!(actual);
+        let actual = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        let actual = client.fetch_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests the decision tells how long to wait until one request has leaked out.
+    #[test]
+    fn leaky_bucket_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(4, Duration::from_secs(1)).with_burst(2);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test4";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        for remaining in (0..2).rev() {
+            let actual = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+            assert!(actual.allowed);
+            assert_eq!(actual.limit, 2);
+            assert_eq!(actual.remaining, remaining);
+        }
+
+        // throttled, one request leaks out every 250ms
+        let actual = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_millis(250));
+
+        // retry after
+        clock.advance(actual.retry_after);
+
+        let actual = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        let actual = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests the bucket drains at the rate of the rule over several seconds.
+    #[test]
+    fn leaky_bucket_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(2, Duration::from_secs(1));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test4";
+        let resource = "data";
+        let subject = "andy";
+
+        // fill up the bucket
+        while client
+            .record_leaky_bucket(key_prefix, resource, subject, &rule)?
+            .allowed
+        {}
+
+        // act
+        let mut allowed = 0;
+        for _ in 0..150 {
+            if client
+                .record_leaky_bucket(key_prefix, resource, subject, &rule)?
+                .allowed
+            {
+                allowed += 1;
+            }
+            clock.advance(Duration::from_millis(20));
+        }
+
+        // assert, one request leaks out every 500ms in the 3s
+        assert_eq!(allowed, 5);
+
+        Ok(())
+    }
+
+    /// Tests the shaping schedules the requests at the rate of the rule instead of rejecting them.
+    #[test]
+    fn leaky_bucket_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(4, Duration::from_secs(1)).with_burst(1);
+        let max_delay = Duration::from_secs(1);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test4";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let first =
+            client.schedule_leaky_bucket(key_prefix, resource, subject, &rule, max_delay)?;
+        assert!(first.scheduled);
+        assert_eq!(first.delay, Duration::ZERO);
+
+        // one request leaks out every 250ms
+        for slot in 1..=4 {
+            let actual =
+                client.schedule_leaky_bucket(key_prefix, resource, subject, &rule, max_delay)?;
+            assert!(actual.scheduled);
+            assert!(actual.delay <= Duration::from_millis(250) * slot);
+            assert!(actual.delay + Duration::from_millis(50) >= Duration::from_millis(250) * slot);
+            assert!(actual.process_at >= first.process_at + Duration::from_millis(240) * slot);
+        }
+
+        // queued up beyond the max delay
+        let actual =
+            client.schedule_leaky_bucket(key_prefix, resource, subject, &rule, max_delay)?;
+        assert!(!actual.scheduled);
+        assert!(actual.delay <= Duration::from_millis(250));
+
+        // the scheduled requests fill the bucket for the other checks too
+        let actual = client.record_leaky_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests waiting for the slots spaces the requests by the rate of the rule.
+    #[test]
+    fn leaky_bucket_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(5, Duration::from_secs(1)).with_burst(1);
+        let max_delay = Duration::from_secs(10);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test4";
+        let resource = "data";
+        let subject = "andy";
+
+        // act
+        let start = Instant::now();
+        for _ in 0..4 {
+            let actual =
+                client.wait_leaky_bucket(key_prefix, resource, subject, &rule, max_delay)?;
+            assert!(actual.scheduled);
+        }
+
+        // assert, the first request is processed at once and the others every 200ms
+        assert!(start.elapsed() >= Duration::from_millis(590));
+        assert!(start.elapsed() < Duration::from_millis(900));
+
+        Ok(())
+    }
+
+    /// Tests the weighted requests wait until the bucket has room for their cost, with a mock clock.
+    #[test]
+    fn leaky_bucket_redis_case6() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(1));
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test4";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let actual =
+            client.record_leaky_bucket_weighted(key_prefix, resource, subject, &rule, 6)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 4);
+
+        // less than the cost remains
+        let actual =
+            client.record_leaky_bucket_weighted(key_prefix, resource, subject, &rule, 5)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.remaining, 4);
+        assert_eq!(actual.retry_after, Duration::from_millis(100));
+
+        clock.advance(actual.retry_after);
+        let actual =
+            client.record_leaky_bucket_weighted(key_prefix, resource, subject, &rule, 5)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests no room remains in a bucket filled over its capacity, by the scheduled requests or
+    /// by the requests of a grant which is gone.
+    #[test]
+    fn leaky_bucket_redis_case7() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(60)).with_burst(2);
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let mut conn = redis::Client::open(CONN)?.get_connection()?;
+        let key_prefix = "test4";
+        let resource = "data";
+        let max_delay = Duration::from_secs(600);
+
+        // act && assert
+        for _ in 0..5 {
+            let actual =
+                client.schedule_leaky_bucket(key_prefix, resource, "andy", &rule, max_delay)?;
+            assert!(actual.scheduled);
+        }
+        let actual = client.fetch_leaky_bucket(key_prefix, resource, "andy", &rule)?;
+        assert_eq!(actual.remaining, 0);
 
-        let count = client_requester.fetch_leaky_bucket(key_prefix, resource, subject, size)?;
-        assert_eq!(count, 1);
+        client.grant_extra_quota(key_prefix, resource, "bob", 5, Duration::from_secs(60))?;
+        for _ in 0..7 {
+            let actual = client.record_leaky_bucket(key_prefix, resource, "bob", &rule)?;
+            assert!(actual.allowed);
+        }
+        // the grant expires
+        let _: u64 = redis::cmd("DEL")
+            .arg("test4:data:{bob}:grant")
+            .query(&mut conn)?;
+        let actual = client.record_leaky_bucket(key_prefix, resource, "bob", &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.remaining, 0);
 
-        // final
-        let _ = tx.send(());
-        let _ = consumer.join();
         Ok(())
     }
 }
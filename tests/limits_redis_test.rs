@@ -0,0 +1,214 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<redis::Connection> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::limits::Limit;
+    use rrr::rate_limiter::Algorithm;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+
+    /// Integration: Allowed -> Denied by the user -> Allowed for another user -> Denied by the organisation
+    #[test]
+    fn limits_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let minute = Duration::from_secs(60);
+        let limits = |user: &str| {
+            vec![
+                Limit::new(Algorithm::FixedWindow, "user", user, Rule::new(2, minute)),
+                Limit::new(Algorithm::SlidingLog, "org", "acme", Rule::new(3, minute)),
+                Limit::new(
+                    Algorithm::TokenBucket,
+                    "global",
+                    "all",
+                    Rule::new(10, minute),
+                ),
+            ]
+        };
+
+        // act && assert
+        let actual = client.record_limits("test18", &limits("andy"), 1)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.binding, 0);
+        assert_eq!(actual.binding_decision().remaining, 1);
+        let remaining: Vec<_> = actual.decisions.iter().map(|d| d.remaining).collect();
+        assert_eq!(remaining, vec![1, 2, 9]);
+
+        let actual = client.record_limits("test18", &limits("andy"), 1)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.binding, 0);
+
+        // denied by the user, without consuming the organisation
+        let actual = client.record_limits("test18", &limits("andy"), 1)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.binding, 0);
+        assert!(actual.binding_decision().retry_after > Duration::ZERO);
+
+        let actual = client.fetch_sliding_log("test18", "org", "acme", &Rule::new(3, minute))?;
+        assert_eq!(actual.remaining, 1);
+
+        let actual = client.record_limits("test18", &limits("bob"), 1)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.binding, 1);
+        assert_eq!(actual.decisions[1].remaining, 0);
+        assert_eq!(actual.decisions[2].remaining, 7);
+
+        // denied by the organisation
+        let actual = client.record_limits("test18", &limits("carol"), 1)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.binding, 1);
+        assert!(actual.decisions[0].allowed);
+
+        let actual = client.fetch_fixed_window("test18", "user", "carol", &Rule::new(2, minute))?;
+        assert_eq!(actual.remaining, 2);
+
+        Ok(())
+    }
+
+    /// Tests the binding limit is the denying one to wait the longest for.
+    #[test]
+    fn limits_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let limits = [
+            Limit::new(
+                Algorithm::Gcra,
+                "second",
+                "andy",
+                Rule::new(1, Duration::from_secs(1)),
+            ),
+            Limit::new(
+                Algorithm::SlidingWindow,
+                "hour",
+                "andy",
+                Rule::new(1, Duration::from_secs(3600)),
+            ),
+        ];
+        client.record_limits("test18", &limits, 1)?;
+
+        // act
+        let actual = client.record_limits("test18", &limits, 1)?;
+
+        // assert
+        assert!(!actual.allowed);
+        assert!(!actual.decisions[0].allowed);
+        assert!(!actual.decisions[1].allowed);
+        assert_eq!(actual.binding, 1);
+        assert!(actual.binding_decision().retry_after > Duration::from_secs(60));
+
+        Ok(())
+    }
+
+    /// Tests the limits are peeked at without recording anything, with the decision recording would get.
+    #[test]
+    fn limits_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        let mut conn = initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(MockClock::new(Duration::from_secs(1_700_000_000)));
+        let limits = [
+            Limit::new(
+                Algorithm::LeakyBucket,
+                "user",
+                "andy",
+                Rule::new(5, Duration::from_secs(60)),
+            ),
+            Limit::new(
+                Algorithm::FixedWindow,
+                "org",
+                "acme",
+                Rule::new(4, Duration::from_secs(60)),
+            ),
+        ];
+
+        // act
+        let peeked = client.peek_limits("test18", &limits, 3)?;
+
+        // assert
+        let keys: u64 = redis::cmd("DBSIZE").query(&mut conn)?;
+        assert_eq!(keys, 0);
+
+        let actual = client.record_limits("test18", &limits, 3)?;
+        assert_eq!(actual, peeked);
+        assert_eq!(actual.binding, 1);
+        assert_eq!(actual.binding_decision().remaining, 1);
+
+        Ok(())
+    }
+
+    /// Tests the error when no limit is given, a subject is limited twice, or the cost exceeds the
+    /// capacity of one of them.
+    #[test]
+    fn limits_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let limits = [
+            Limit::new(
+                Algorithm::FixedWindow,
+                "user",
+                "andy",
+                Rule::new(5, Duration::from_secs(60)),
+            ),
+            Limit::new(
+                Algorithm::TokenBucket,
+                "org",
+                "acme",
+                Rule::new(5, Duration::from_secs(60)).with_burst(2),
+            ),
+        ];
+
+        // act && assert
+        let actual = client.record_limits("test18", &[], 1);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        let twice = [
+            Limit::new(
+                Algorithm::FixedWindow,
+                "user",
+                "andy",
+                Rule::new(10, Duration::from_secs(1)),
+            ),
+            Limit::new(
+                Algorithm::SlidingLog,
+                "user",
+                "andy",
+                Rule::new(500, Duration::from_secs(60)),
+            ),
+        ];
+        let actual = client.record_limits("test18", &twice, 1);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        let actual = client.record_limits("test18", &limits, 3);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        Ok(())
+    }
+}
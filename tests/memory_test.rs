@@ -0,0 +1,267 @@
+#[cfg(test)]
+mod tests {
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter::Algorithm;
+    use rrr::rate_limiter_memory::RateLimiterMemory;
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const ALGORITHMS: [&str; 6] = [
+        "fixed_window",
+        "sliding_log",
+        "sliding_window",
+        "leaky_bucket",
+        "token_bucket",
+        "gcra",
+    ];
+
+    /// Integration: Initiated -> Throttled -> Reset -> Refilled, for every algorithm in memory.
+    #[test]
+    fn memory_case1() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // arrange
+            let rule = Rule::new(1, Duration::from_secs(1));
+            let algorithm: Algorithm = name.parse()?;
+            let mut limiter = algorithm.build(RateLimiterMemory::new(), "test14", rule);
+            let resource = "data";
+            let subject = "andy";
+
+            // act && assert
+            let actual = limiter.check(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, 0, "{name}");
+
+            // throttled
+            let actual = limiter.check(resource, subject)?;
+            assert!(!actual.allowed, "{name}");
+            assert!(actual.retry_after > Duration::ZERO, "{name}");
+
+            let actual = limiter.fetch(resource, subject)?;
+            assert!(!actual.allowed, "{name}");
+
+            // reset
+            limiter.reset(resource, subject)?;
+
+            let actual = limiter.fetch(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, 1, "{name}");
+
+            // refilled
+            let actual = limiter.check(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+        }
+
+        Ok(())
+    }
+
+    /// Integration: Initiated -> Throttled -> Cool Down -> Refilled, with a sub-second rule.
+    #[test]
+    fn memory_case2() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // arrange
+            let rule = Rule::new(2, Duration::from_millis(200));
+            let algorithm: Algorithm = name.parse()?;
+            let mut limiter = algorithm.build(RateLimiterMemory::new(), "test14", rule);
+            let resource = "data";
+            let subject = "andy";
+
+            // act && assert
+            let mut actual = limiter.check(resource, subject)?;
+            while actual.allowed {
+                actual = limiter.check(resource, subject)?;
+            }
+
+            // throttled
+            assert!(actual.retry_after <= Duration::from_millis(400), "{name}");
+
+            // cool down
+            std::thread::sleep(actual.retry_after);
+
+            // refilled
+            let actual = limiter.check(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+        }
+
+        Ok(())
+    }
+
+    /// Tests the clones of the limiter share the state, and the concurrent requests never exceed the limit.
+    #[test]
+    fn memory_case3() -> Result<(), RateLimitError> {
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(3600)).with_burst(100);
+        let client = RateLimiterMemory::with_shards(4);
+
+        // act
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let client = client.clone();
+                std::thread::spawn(move || -> Result<usize, RateLimitError> {
+                    let mut allowed = 0;
+                    for _ in 0..50 {
+                        if client
+                            .record_token_bucket("test14", "data", "andy", &rule)?
+                            .allowed
+                        {
+                            allowed += 1;
+                        }
+                    }
+                    Ok(allowed)
+                })
+            })
+            .collect();
+        let mut allowed = 0;
+        for worker in workers {
+            allowed += worker.join().expect("the worker should not panic")?;
+        }
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        let actual = client.fetch_token_bucket("test14", "data", "andy", &rule)?;
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests the leaky bucket shapes the traffic in memory.
+    #[test]
+    fn memory_case4() -> Result<(), RateLimitError> {
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(1)).with_burst(1);
+        let client = RateLimiterMemory::new();
+        let max_delay = Duration::from_millis(250);
+
+        // act && assert
+        let actual = client.schedule_leaky_bucket("test14", "data", "andy", &rule, max_delay)?;
+        assert!(actual.scheduled);
+        assert_eq!(actual.delay, Duration::ZERO);
+
+        let actual = client.schedule_leaky_bucket("test14", "data", "andy", &rule, max_delay)?;
+        assert!(actual.scheduled);
+        assert!(actual.delay > Duration::ZERO);
+        assert!(actual.delay <= Duration::from_millis(100));
+
+        let actual = client.schedule_leaky_bucket("test14", "data", "andy", &rule, max_delay)?;
+        assert!(actual.scheduled);
+
+        // too long to wait
+        let actual = client.schedule_leaky_bucket("test14", "data", "andy", &rule, max_delay)?;
+        assert!(!actual.scheduled);
+
+        Ok(())
+    }
+
+    /// Tests the error when two algorithms keep different values under the same key, like on Redis.
+    #[test]
+    fn memory_case5() -> Result<(), RateLimitError> {
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(1));
+        let client = RateLimiterMemory::new();
+
+        // act
+        client.record_sliding_log("test14", "data", "andy", &rule)?;
+        let actual = client.record_leaky_bucket("test14", "data", "andy", &rule);
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::WrongType(_))));
+
+        Ok(())
+    }
+
+    /// Tests the boundaries of the fixed window and the token bucket with a mock clock.
+    #[test]
+    fn memory_case6() -> Result<(), RateLimitError> {
+        // arrange
+        let rule = Rule::new(2, Duration::from_secs(1));
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = RateLimiterMemory::new();
+        client.clock = Arc::new(clock.clone());
+
+        // act && assert
+        client.record_fixed_window("test14", "data", "andy", &rule)?;
+        client.record_fixed_window("test14", "data", "andy", &rule)?;
+        clock.advance(Duration::from_millis(999));
+        let actual = client.record_fixed_window("test14", "data", "andy", &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_millis(1));
+
+        // the next window
+        clock.advance(Duration::from_millis(1));
+        let actual = client.record_fixed_window("test14", "data", "andy", &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 1);
+
+        client.record_token_bucket("test14", "data", "andy", &rule)?;
+        client.record_token_bucket("test14", "data", "andy", &rule)?;
+        clock.advance(Duration::from_millis(499));
+        let actual = client.record_token_bucket("test14", "data", "andy", &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_millis(1));
+
+        // one token refilled
+        clock.advance(Duration::from_millis(1));
+        let actual = client.record_token_bucket("test14", "data", "andy", &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests the limiter peeks at the decision of a weighted check, without recording it.
+    #[test]
+    fn memory_case7() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // arrange
+            let rule = Rule::new(5, Duration::from_secs(60));
+            let algorithm: Algorithm = name.parse()?;
+            let mut limiter = algorithm.build(RateLimiterMemory::new(), "test14", rule);
+            let resource = "data";
+            let subject = "andy";
+
+            // act && assert
+            let actual = limiter.peek_weighted(resource, subject, 3)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, 2, "{name}");
+
+            let actual = limiter.peek_weighted(resource, subject, 5)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, 0, "{name}");
+
+            // nothing has been recorded by peeking
+            let actual = limiter.check_weighted(resource, subject, 5)?;
+            assert!(actual.allowed, "{name}");
+
+            let actual = limiter.peek_weighted(resource, subject, 1)?;
+            assert!(!actual.allowed, "{name}");
+        }
+
+        Ok(())
+    }
+
+    /// Tests every algorithm checks and resets at the start of the clock, before one period.
+    #[test]
+    fn memory_case8() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // arrange
+            let rule = Rule::new(2, Duration::from_secs(60));
+            let algorithm: Algorithm = name.parse()?;
+            let mut client = RateLimiterMemory::new();
+            client.clock = Arc::new(MockClock::default());
+            let mut limiter = algorithm.build(client, "test14", rule);
+
+            // act && assert
+            let actual = limiter.check("data", "andy")?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, 1, "{name}");
+
+            limiter.reset("data", "andy")?;
+            let actual = limiter.check("data", "andy")?;
+            assert_eq!(actual.remaining, 1, "{name}");
+        }
+
+        Ok(())
+    }
+}
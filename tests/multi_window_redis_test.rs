@@ -0,0 +1,176 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<redis::Connection> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter::Algorithm;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::MultiWindowRule;
+    use std::sync::Arc;
+    use std::time::{self, Duration};
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+
+    /// Integration: Allowed -> Allowed -> Denied by the second -> Allowed -> Denied by the minute
+    #[test]
+    fn multi_window_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        // the start of a minute
+        let start = Duration::from_secs(1_700_000_040);
+        let clock = MockClock::new(start);
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let rule = MultiWindowRule::new()
+            .with_window(2, Duration::from_secs(1))
+            .with_window(3, Duration::from_secs(60));
+        let record = |client: &mut rate_limiter_redis::RateLimiterRedis| {
+            client.record_multi_window(Algorithm::FixedWindow, "test20", "api", "andy", &rule)
+        };
+
+        // act && assert
+        let actual = record(&mut client)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 1);
+        assert_eq!(
+            actual.reset_at,
+            time::UNIX_EPOCH + start + Duration::from_secs(60)
+        );
+
+        let actual = record(&mut client)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        let actual = record(&mut client)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_secs(1));
+
+        clock.advance(Duration::from_secs(1));
+        let actual = record(&mut client)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        clock.advance(Duration::from_secs(1));
+        let actual = record(&mut client)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.limit, 3);
+        assert_eq!(actual.retry_after, Duration::from_secs(58));
+
+        Ok(())
+    }
+
+    /// Tests a request denied by one window is counted in none, with the sliding algorithms.
+    #[test]
+    fn multi_window_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let rule = MultiWindowRule::new()
+            .with_window(1, Duration::from_secs(10))
+            .with_window(5, Duration::from_secs(3600));
+
+        for algorithm in [Algorithm::SlidingLog, Algorithm::SlidingWindow] {
+            // act
+            client.record_multi_window(algorithm, "test20", "api", "andy", &rule)?;
+            let denied = client.record_multi_window(algorithm, "test20", "api", "andy", &rule)?;
+            let actual = client.fetch_multi_window(algorithm, "test20", "api", "bob", &rule)?;
+
+            // assert
+            assert!(!denied.allowed);
+            assert_eq!(actual.remaining, 1);
+            let hour = MultiWindowRule::new().with_window(5, Duration::from_secs(3600));
+            let actual = client.fetch_multi_window(algorithm, "test20", "api", "andy", &hour)?;
+            assert_eq!(actual.remaining, 4);
+
+            initialize_redis()?;
+        }
+
+        Ok(())
+    }
+
+    /// Tests peeking records nothing, and the units granted to the subject raise every window.
+    #[test]
+    fn multi_window_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        let mut conn = initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let rule = MultiWindowRule::new()
+            .with_window(1, Duration::from_secs(10))
+            .with_window(2, Duration::from_secs(3600));
+
+        // act && assert
+        let actual =
+            client.peek_multi_window(Algorithm::FixedWindow, "test20", "api", "andy", &rule, 1)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+        let keys: u64 = redis::cmd("DBSIZE").query(&mut conn)?;
+        assert_eq!(keys, 0);
+
+        client.grant_extra_quota("test20", "api", "andy", 1, Duration::from_secs(60))?;
+        client.record_multi_window(Algorithm::FixedWindow, "test20", "api", "andy", &rule)?;
+        let actual =
+            client.record_multi_window(Algorithm::FixedWindow, "test20", "api", "andy", &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests the error of a bucket algorithm, no window, two windows of the same size, or a cost
+    /// over the limit of a window.
+    #[test]
+    fn multi_window_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let rule = MultiWindowRule::new()
+            .with_window(2, Duration::from_secs(1))
+            .with_window(100, Duration::from_secs(60));
+        let invalid = [
+            (Algorithm::TokenBucket, rule.clone()),
+            (Algorithm::FixedWindow, MultiWindowRule::new()),
+            (
+                Algorithm::SlidingLog,
+                rule.clone().with_window(5, Duration::from_secs(60)),
+            ),
+        ];
+
+        // act && assert
+        for (algorithm, rule) in invalid {
+            let actual = client.record_multi_window(algorithm, "test20", "api", "andy", &rule);
+            assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+        }
+
+        let actual = client.record_multi_window_weighted(
+            Algorithm::SlidingWindow,
+            "test20",
+            "api",
+            "andy",
+            &rule,
+            3,
+        );
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        Ok(())
+    }
+}
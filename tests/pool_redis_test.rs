@@ -0,0 +1,126 @@
+// NOTE: cargo test --all --all-features -- --test-threads 1
+#![cfg(feature = "pool")]
+
+fn initialize_redis() -> redis::RedisResult<()> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis_pool::{PoolConfig, RateLimiterRedisPool};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+    const THREADS: usize = 8;
+    const REQUESTS_PER_THREAD: usize = 50;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    /// Integration: Initiated -> Throttled -> Reset -> Refilled
+    #[test]
+    fn pool_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(1));
+        let client = RateLimiterRedisPool::open(CONN)?;
+        let key_prefix = "test11";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        assert_send_sync::<RateLimiterRedisPool>();
+
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        // throttled
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+
+        // reset
+        client.reset_token_bucket(key_prefix, resource, subject)?;
+
+        let actual = client.fetch_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 1);
+
+        // refilled
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests one limiter shared by many threads never exceeds the limit.
+    #[test]
+    fn pool_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(100, Duration::from_secs(100));
+        let config = PoolConfig {
+            max_size: 4,
+            ..PoolConfig::default()
+        };
+        let client = Arc::new(RateLimiterRedisPool::open_with_config(CONN, config)?);
+
+        // act
+        let mut workers = Vec::with_capacity(THREADS);
+        for _ in 0..THREADS {
+            let client = client.clone();
+            workers.push(std::thread::spawn(
+                move || -> Result<usize, RateLimitError> {
+                    let mut allowed = 0;
+                    for _ in 0..REQUESTS_PER_THREAD {
+                        if client
+                            .record_sliding_log("test11", "data", "andy", &rule)?
+                            .allowed
+                        {
+                            allowed += 1;
+                        }
+                    }
+                    Ok(allowed)
+                },
+            ));
+        }
+
+        let mut allowed = 0;
+        for worker in workers {
+            allowed += worker.join().expect("the worker should not panic")?;
+        }
+
+        // assert
+        assert_eq!(allowed, 100);
+
+        Ok(())
+    }
+
+    /// Tests the error when no connection can be made before the connection timeout.
+    #[test]
+    fn pool_redis_case3() {
+        // arrange
+        let config = PoolConfig {
+            max_size: 1,
+            connection_timeout: Duration::from_millis(200),
+            ..PoolConfig::default()
+        };
+
+        // act
+        let actual = RateLimiterRedisPool::open_with_config("redis://127.0.0.1:1/", config);
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::Connection(_))));
+    }
+}
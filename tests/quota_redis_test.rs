@@ -0,0 +1,201 @@
+// NOTE: cargo test --all --all-features -- --test-threads 1
+#![cfg(feature = "calendar")]
+
+fn initialize_redis() -> redis::RedisResult<redis::Connection> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::quota::{CalendarPeriod, Quota, Tz};
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use std::sync::Arc;
+    use std::time::{self, Duration};
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+
+    fn at(secs: u64) -> time::SystemTime {
+        time::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    /// Integration: Allowed -> Allowed -> Denied until the billing day -> Allowed in the next month
+    #[test]
+    fn quota_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        // 2024-03-10T00:00:00Z
+        let clock = MockClock::new(Duration::from_secs(1_710_028_800));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let quota = Quota::new(2, CalendarPeriod::Month).with_anchor_day(15);
+
+        // act && assert
+        let actual = client.record_quota("test19", "plan", "andy", &quota)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 1);
+        // 2024-03-15T00:00:00Z
+        assert_eq!(actual.reset_at, at(1_710_460_800));
+
+        let actual = client.record_quota("test19", "plan", "andy", &quota)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        let actual = client.record_quota("test19", "plan", "andy", &quota)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_secs(5 * 86_400));
+
+        clock.advance(Duration::from_secs(5 * 86_400));
+        let actual = client.record_quota("test19", "plan", "andy", &quota)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 1);
+
+        Ok(())
+    }
+
+    /// Tests the monthly periods start on the last day of the months shorter than the anchor day.
+    #[test]
+    fn quota_redis_case2() -> Result<(), RateLimitError> {
+        // arrange
+        let quota = Quota::new(100, CalendarPeriod::Month).with_anchor_day(31);
+
+        // act && assert
+        // 2024-02-20T12:00:00Z, from 2024-01-31 to 2024-02-29
+        let actual = quota.period_at(at(1_708_430_400))?;
+        assert_eq!(actual, (at(1_706_659_200), at(1_709_164_800)));
+
+        // 2024-03-05T00:00:00Z, from 2024-02-29 to 2024-03-31
+        let actual = quota.period_at(at(1_709_596_800))?;
+        assert_eq!(actual, (at(1_709_164_800), at(1_711_843_200)));
+
+        Ok(())
+    }
+
+    /// Tests the days start at the local midnight of the timezone, and follow its daylight saving time.
+    #[test]
+    fn quota_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        // 2024-01-01T14:00:00Z, 23:00 in Tokyo
+        let clock = MockClock::new(Duration::from_secs(1_704_117_600));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let quota = Quota::new(1, CalendarPeriod::Day).with_timezone(Tz::Asia__Tokyo);
+
+        // act && assert
+        let actual = client.record_quota("test19", "daily", "andy", &quota)?;
+        assert!(actual.allowed);
+        let actual = client.record_quota("test19", "daily", "andy", &quota)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_secs(3600));
+
+        // 01:00 on the next day in Tokyo, the same day in UTC
+        clock.advance(Duration::from_secs(2 * 3600));
+        let actual = client.record_quota("test19", "daily", "andy", &quota)?;
+        assert!(actual.allowed);
+
+        // 2024-03-10T16:00:00Z, the day the clocks spring forward in New York lasts 23 hours
+        let quota = Quota::new(1, CalendarPeriod::Day).with_timezone(Tz::America__New_York);
+        let actual = quota.period_at(at(1_710_086_400))?;
+        assert_eq!(actual, (at(1_710_046_800), at(1_710_129_600)));
+
+        Ok(())
+    }
+
+    /// Tests the weeks start on the anchor weekday, and the hours on the hour.
+    #[test]
+    fn quota_redis_case4() -> Result<(), RateLimitError> {
+        // arrange
+        let weekly = Quota::new(100, CalendarPeriod::Week).with_anchor_day(7);
+        let hourly = Quota::new(100, CalendarPeriod::Hour).with_timezone(Tz::Asia__Kolkata);
+
+        // act && assert
+        // Wednesday 2024-01-03T10:00:00Z, from Sunday 2023-12-31 to Sunday 2024-01-07
+        let actual = weekly.period_at(at(1_704_276_000))?;
+        assert_eq!(actual, (at(1_703_980_800), at(1_704_585_600)));
+
+        // Sunday 2024-01-07T00:00:00Z starts the next week
+        let actual = weekly.period_at(at(1_704_585_600))?;
+        assert_eq!(actual, (at(1_704_585_600), at(1_705_190_400)));
+
+        // 15:30 in Kolkata (UTC+05:30) at 2024-01-03T10:00:00Z
+        let actual = hourly.period_at(at(1_704_276_000))?;
+        assert_eq!(actual, (at(1_704_274_200), at(1_704_277_800)));
+
+        Ok(())
+    }
+
+    /// Tests fetching and peeking record nothing, the weighted requests and resetting the period.
+    #[test]
+    fn quota_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(MockClock::new(Duration::from_secs(1_700_000_000)));
+        let quota = Quota::new(10, CalendarPeriod::Month);
+
+        // act && assert
+        let actual = client.peek_quota("test19", "plan", "andy", &quota, 4)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 6);
+        let actual = client.fetch_quota("test19", "plan", "andy", &quota)?;
+        assert_eq!(actual.remaining, 10);
+
+        let actual = client.record_quota_weighted("test19", "plan", "andy", &quota, 7)?;
+        assert_eq!(actual.remaining, 3);
+        let actual = client.record_quota_weighted("test19", "plan", "andy", &quota, 4)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.remaining, 3);
+
+        client.reset_quota("test19", "plan", "andy", &quota)?;
+        let actual = client.fetch_quota("test19", "plan", "andy", &quota)?;
+        assert_eq!(actual.remaining, 10);
+
+        Ok(())
+    }
+
+    /// Tests the error of an anchor day out of the period, a zero limit, or a cost over the limit.
+    #[test]
+    fn quota_redis_case6() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let invalid = [
+            Quota::new(10, CalendarPeriod::Month).with_anchor_day(0),
+            Quota::new(10, CalendarPeriod::Month).with_anchor_day(32),
+            Quota::new(10, CalendarPeriod::Week).with_anchor_day(8),
+            Quota::new(0, CalendarPeriod::Day),
+        ];
+
+        // act && assert
+        for quota in invalid {
+            let actual = client.record_quota("test19", "plan", "andy", &quota);
+            assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+        }
+
+        let quota = Quota::new(10, CalendarPeriod::Hour);
+        let actual = client.record_quota_weighted("test19", "plan", "andy", &quota, 11);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        Ok(())
+    }
+}
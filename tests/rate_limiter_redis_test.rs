@@ -0,0 +1,316 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<redis::Connection> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::backend::Backend;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter::Algorithm;
+    use rrr::rate_limiter_memory::RateLimiterMemory;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+    const ALGORITHMS: [&str; 6] = [
+        "fixed_window",
+        "sliding_log",
+        "sliding_window",
+        "leaky_bucket",
+        "token_bucket",
+        "gcra",
+    ];
+
+    /// Integration: Initiated -> Throttled -> Reset -> Refilled, for every algorithm chosen by name.
+    #[test]
+    fn rate_limiter_redis_case1() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // prev
+            initialize_redis()?;
+
+            // arrange
+            let rule = Rule::new(1, Duration::from_secs(1));
+            let client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+            let algorithm: Algorithm = name.parse()?;
+            let mut limiter = algorithm.build(client, "test8", rule);
+            let resource = "data";
+            let subject = "andy";
+
+            // act && assert
+            let actual = limiter.check(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+
+            // throttled
+            let actual = limiter.check(resource, subject)?;
+            assert!(!actual.allowed, "{name}");
+
+            let actual = limiter.fetch(resource, subject)?;
+            assert!(!actual.allowed, "{name}");
+
+            // reset
+            limiter.reset(resource, subject)?;
+
+            let actual = limiter.fetch(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, 1, "{name}");
+
+            // refilled
+            let actual = limiter.check(resource, subject)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(algorithm.to_string(), name);
+        }
+
+        Ok(())
+    }
+
+    /// Tests the error when the algorithm is unknown.
+    #[test]
+    fn rate_limiter_redis_case2() {
+        // act
+        let actual = "round_robin".parse::<Algorithm>();
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+    }
+
+    /// Tests the memory backend makes the same decisions as Redis, for every algorithm.
+    #[test]
+    fn rate_limiter_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(3, Duration::from_secs(3600)).with_burst(4);
+        let mut redis = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let mut memory = RateLimiterMemory::new();
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        for name in ALGORITHMS {
+            let algorithm: Algorithm = name.parse()?;
+            for _ in 0..6 {
+                let expected = redis.record(algorithm, "test8", resource, subject, &rule)?;
+                let actual = memory.record(algorithm, "test8", resource, subject, &rule)?;
+                assert_eq!(actual.allowed, expected.allowed, "{name}");
+                assert_eq!(actual.limit, expected.limit, "{name}");
+                assert_eq!(actual.remaining, expected.remaining, "{name}");
+            }
+
+            let expected = redis.fetch(algorithm, "test8", resource, subject, &rule)?;
+            let actual = memory.fetch(algorithm, "test8", resource, subject, &rule)?;
+            assert_eq!(actual.allowed, expected.allowed, "{name}");
+            assert_eq!(actual.remaining, expected.remaining, "{name}");
+
+            redis.reset(algorithm, "test8", resource, subject, &rule)?;
+            memory.reset(algorithm, "test8", resource, subject, &rule)?;
+        }
+
+        Ok(())
+    }
+
+    /// Tests Redis and memory read the same mock clock and make exactly the same decisions
+    /// across the boundaries of the windows and refills, without sleeping.
+    #[test]
+    fn rate_limiter_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(2, Duration::from_secs(10));
+        let clock = MockClock::new(Duration::from_millis(1_700_000_000_000));
+        let mut redis = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        redis.time_source = TimeSource::Client;
+        redis.clock = Arc::new(clock.clone());
+        let mut memory = RateLimiterMemory::new();
+        memory.clock = Arc::new(clock.clone());
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        for name in ALGORITHMS {
+            let algorithm: Algorithm = name.parse()?;
+            for step in [0, 1, 2_999, 1, 4_999, 1, 9_998, 1, 1] {
+                clock.advance(Duration::from_millis(step));
+
+                let expected = redis.record(algorithm, "test8", resource, subject, &rule)?;
+                let actual = memory.record(algorithm, "test8", resource, subject, &rule)?;
+                assert_eq!(actual, expected, "{name} at +{step}ms");
+            }
+
+            redis.reset(algorithm, "test8", resource, subject, &rule)?;
+            memory.reset(algorithm, "test8", resource, subject, &rule)?;
+        }
+
+        Ok(())
+    }
+
+    /// Tests Redis and memory make the same decisions for weighted requests, and the limiter
+    /// checks them by `check_weighted`.
+    #[test]
+    fn rate_limiter_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(10)).with_burst(12);
+        let clock = MockClock::new(Duration::from_millis(1_700_000_000_000));
+        let mut redis = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        redis.time_source = TimeSource::Client;
+        redis.clock = Arc::new(clock.clone());
+        let mut memory = RateLimiterMemory::new();
+        memory.clock = Arc::new(clock.clone());
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        for name in ALGORITHMS {
+            let algorithm: Algorithm = name.parse()?;
+            for (step, cost) in [
+                (0, 3),
+                (1, 5),
+                (500, 4),
+                (2_999, 2),
+                (1, 7),
+                (6_499, 9),
+                (1, 1),
+            ] {
+                clock.advance(Duration::from_millis(step));
+
+                let expected =
+                    redis.record_weighted(algorithm, "test8", resource, subject, &rule, cost)?;
+                let actual =
+                    memory.record_weighted(algorithm, "test8", resource, subject, &rule, cost)?;
+                assert_eq!(actual, expected, "{name} at +{step}ms for {cost}");
+            }
+
+            redis.reset(algorithm, "test8", resource, subject, &rule)?;
+            let mut limiter = algorithm.build(memory.clone(), "test8", rule);
+            limiter.reset(resource, subject)?;
+            let actual = limiter.check_weighted(resource, subject, 10)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, actual.limit - 10, "{name}");
+        }
+
+        Ok(())
+    }
+
+    /// Tests the peek of every algorithm leaves no key behind, and decides exactly like the
+    /// next record, on Redis and in memory, across expired requests and refills.
+    #[test]
+    fn rate_limiter_redis_case6() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // prev
+            let mut conn = initialize_redis()?;
+
+            // arrange
+            let rule = Rule::new(10, Duration::from_secs(10)).with_burst(12);
+            let clock = MockClock::new(Duration::from_millis(1_700_000_000_000));
+            let mut redis = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+            redis.time_source = TimeSource::Client;
+            redis.clock = Arc::new(clock.clone());
+            let mut memory = RateLimiterMemory::new();
+            memory.clock = Arc::new(clock.clone());
+            let algorithm: Algorithm = name.parse()?;
+            let resource = "data";
+            let subject = "andy";
+
+            // act && assert
+            let actual = redis.peek(algorithm, "test8", resource, subject, &rule, 3)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, actual.limit - 3, "{name}");
+            let keys: u64 = redis::cmd("DBSIZE").query(&mut conn)?;
+            assert_eq!(keys, 0, "{name}");
+
+            for (step, cost) in [(0, 4), (1, 6), (1, 3), (9_998, 2), (1, 5), (10_001, 10)] {
+                clock.advance(Duration::from_millis(step));
+
+                let peeked = redis.peek(algorithm, "test8", resource, subject, &rule, cost)?;
+                let expected =
+                    redis.record_weighted(algorithm, "test8", resource, subject, &rule, cost)?;
+                assert_eq!(peeked, expected, "{name} at +{step}ms for {cost}");
+
+                let peeked = memory.peek(algorithm, "test8", resource, subject, &rule, cost)?;
+                let actual =
+                    memory.record_weighted(algorithm, "test8", resource, subject, &rule, cost)?;
+                assert_eq!(peeked, actual, "{name} at +{step}ms for {cost}");
+                assert_eq!(actual, expected, "{name} at +{step}ms for {cost}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tests the peek of the limiter decides exactly like the next check, while `fetch` tells the
+    /// state before it, on Redis and in memory.
+    #[test]
+    fn rate_limiter_redis_case7() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // prev
+            initialize_redis()?;
+
+            // arrange
+            let rule = Rule::new(2, Duration::from_secs(1));
+            let clock = MockClock::new(Duration::from_millis(1_700_000_000_000));
+            let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+            client.time_source = TimeSource::Client;
+            client.clock = Arc::new(clock.clone());
+            let mut memory = RateLimiterMemory::new();
+            memory.clock = Arc::new(clock.clone());
+            let algorithm: Algorithm = name.parse()?;
+            let mut redis = algorithm.build(client, "test8", rule);
+            let mut memory = algorithm.build(memory, "test8", rule);
+
+            // act && assert
+            for limiter in [&mut redis, &mut memory] {
+                let actual = limiter.fetch("data", "andy")?;
+                assert_eq!(actual.remaining, 2, "{name}");
+
+                for _ in 0..3 {
+                    let peeked = limiter.peek("data", "andy")?;
+                    let actual = limiter.check("data", "andy")?;
+                    assert_eq!(peeked, actual, "{name}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tests only the fetches are sent to the replica, while the peeks read the master.
+    #[test]
+    fn rate_limiter_redis_case8() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(2, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        // another database stands for a replica which has not caught up yet
+        let replica = redis::Client::open("redis://127.0.0.1:6379/1")?;
+        client.replica = Some(replica.get_connection()?);
+
+        // act
+        client.record_token_bucket("test8", "data", "andy", &rule)?;
+
+        // assert
+        let actual = client.peek_token_bucket("test8", "data", "andy", &rule, 1)?;
+        assert_eq!(actual.remaining, 0);
+
+        let actual = client.fetch_token_bucket("test8", "data", "andy", &rule)?;
+        assert_eq!(actual.remaining, 2);
+
+        Ok(())
+    }
+}
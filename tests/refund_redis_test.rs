@@ -0,0 +1,188 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<()> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::backend::Backend;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter::Algorithm;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+
+    const ALGORITHMS: [&str; 6] = [
+        "fixed_window",
+        "sliding_log",
+        "sliding_window",
+        "leaky_bucket",
+        "token_bucket",
+        "gcra",
+    ];
+
+    /// Integration: Throttled -> Refunded -> Allowed, and refunded only once, for every algorithm.
+    #[test]
+    fn refund_redis_case1() -> Result<(), RateLimitError> {
+        for name in ALGORITHMS {
+            // prev
+            initialize_redis()?;
+
+            // arrange
+            let rule = Rule::new(3, Duration::from_secs(60));
+            let algorithm: Algorithm = name.parse()?;
+            let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+            let key_prefix = "test16";
+            let resource = "data";
+            let subject = "andy";
+            client.record_refundable(algorithm, key_prefix, resource, subject, &rule, 1)?;
+
+            // act
+            let (actual, token) =
+                client.record_refundable(algorithm, key_prefix, resource, subject, &rule, 2)?;
+
+            // assert
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, 0, "{name}");
+            let token = token.expect("an allowed request should be refundable");
+            assert_eq!(token.cost(), 2, "{name}");
+
+            let (actual, token_denied) =
+                client.record_refundable(algorithm, key_prefix, resource, subject, &rule, 1)?;
+            assert!(!actual.allowed, "{name}");
+            assert_eq!(token_denied, None, "{name}");
+
+            // refunded
+            assert!(client.refund(&token)?, "{name}");
+            let actual = client.peek(algorithm, key_prefix, resource, subject, &rule, 2)?;
+            assert!(actual.allowed, "{name}");
+            assert_eq!(actual.remaining, 0, "{name}");
+
+            // refunded only once
+            assert!(!client.refund(&token)?, "{name}");
+            let actual = client.peek(algorithm, key_prefix, resource, subject, &rule, 3)?;
+            assert!(!actual.allowed, "{name}");
+        }
+
+        Ok(())
+    }
+
+    /// Tests the refund removes exactly the members of the request from the sliding log.
+    #[test]
+    fn refund_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(5, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let (_, first) =
+            client.record_refundable(Algorithm::SlidingLog, "test16", "data", "andy", &rule, 2)?;
+        client.record_sliding_log_weighted("test16", "data", "andy", &rule, 2)?;
+        let (_, last) =
+            client.record_refundable(Algorithm::SlidingLog, "test16", "data", "andy", &rule, 1)?;
+
+        // act
+        let actual = client.refund(&first.expect("the request should be refundable"))?;
+
+        // assert
+        assert!(actual);
+
+        let conn = &mut client.conn;
+        let actual: Vec<String> = redis::cmd("ZRANGE")
+            .arg("test16:data:{andy}")
+            .arg(0)
+            .arg(-1)
+            .query(conn)?;
+        assert_eq!(actual.len(), 3);
+        assert!(actual.iter().all(|member| !member.ends_with(":1")));
+        assert!(actual.iter().all(|member| !member.ends_with(":2")));
+
+        let actual = client.refund(&last.expect("the request should be refundable"))?;
+        assert!(actual);
+        let actual = client.fetch_sliding_log("test16", "data", "andy", &rule)?;
+        assert_eq!(actual.remaining, 3);
+
+        Ok(())
+    }
+
+    /// Tests nothing is given back once a period has passed since the request.
+    #[test]
+    fn refund_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(2, Duration::from_secs(1));
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let (_, token) =
+            client.record_refundable(Algorithm::TokenBucket, "test16", "data", "andy", &rule, 2)?;
+        let token = token.expect("the request should be refundable");
+
+        // act
+        clock.advance(Duration::from_secs(1));
+        client.record_token_bucket_weighted("test16", "data", "andy", &rule, 2)?;
+        let actual = client.refund(&token)?;
+
+        // assert
+        assert!(!actual);
+
+        let actual = client.fetch_token_bucket("test16", "data", "andy", &rule)?;
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests the concurrent refunds of the same token give the units back once.
+    #[test]
+    fn refund_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let (_, token) =
+            client.record_refundable(Algorithm::FixedWindow, "test16", "data", "andy", &rule, 5)?;
+        let token = token.expect("the request should be refundable");
+
+        // act
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let token = token.clone();
+                std::thread::spawn(move || -> Result<bool, RateLimitError> {
+                    let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+                    client.refund(&token)
+                })
+            })
+            .collect();
+        let mut refunded = 0;
+        for worker in workers {
+            if worker.join().expect("the worker should not panic")? {
+                refunded += 1;
+            }
+        }
+
+        // assert
+        assert_eq!(refunded, 1);
+
+        let actual = client.fetch_fixed_window("test16", "data", "andy", &rule)?;
+        assert_eq!(actual.remaining, 10);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+// NOTE: cargo test --all -- --test-threads 1
+fn initialize_redis() -> redis::RedisResult<redis::Connection> {
+    let redis_address: &str = "redis://127.0.0.1:6379/";
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const CONN: &str = "redis://127.0.0.1:6379/";
+
+    /// Integration: Acquired -> Full -> Released -> Acquired
+    #[test]
+    fn semaphore_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test17";
+        let resource = "report";
+        let subject = "andy";
+        let ttl = Duration::from_secs(60);
+
+        // act && assert
+        let first = client
+            .acquire_lease(key_prefix, resource, subject, 2, ttl)?
+            .expect("the first lease should be acquired");
+        assert_eq!(first.remaining, 1);
+
+        let second = client
+            .acquire_lease(key_prefix, resource, subject, 2, ttl)?
+            .expect("the second lease should be acquired");
+        assert_eq!(second.remaining, 0);
+        assert_ne!(first.id, second.id);
+
+        // full
+        let actual = client.acquire_lease(key_prefix, resource, subject, 2, ttl)?;
+        assert_eq!(actual, None);
+
+        // another subject has its own leases
+        let actual = client.acquire_lease(key_prefix, resource, "bob", 2, ttl)?;
+        assert!(actual.is_some());
+
+        // released
+        assert!(client.release_lease(key_prefix, resource, subject, &first.id)?);
+        assert!(!client.release_lease(key_prefix, resource, subject, &first.id)?);
+
+        let actual = client.acquire_lease(key_prefix, resource, subject, 2, ttl)?;
+        assert!(actual.is_some());
+
+        Ok(())
+    }
+
+    /// Tests the lease of a crashed holder expires, and frees its slot.
+    #[test]
+    fn semaphore_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let ttl = Duration::from_secs(1);
+        let crashed = client
+            .acquire_lease("test17", "report", "andy", 1, ttl)?
+            .expect("the lease should be acquired");
+        assert_eq!(
+            crashed.expires_at,
+            std::time::UNIX_EPOCH + Duration::from_millis(1_700_000_001_000)
+        );
+
+        // act && assert
+        clock.advance(Duration::from_millis(999));
+        let actual = client.acquire_lease("test17", "report", "andy", 1, ttl)?;
+        assert_eq!(actual, None);
+
+        // expired
+        clock.advance(Duration::from_millis(1));
+        let actual = client.acquire_lease("test17", "report", "andy", 1, ttl)?;
+        assert!(actual.is_some());
+
+        let actual = client.release_lease("test17", "report", "andy", &crashed.id)?;
+        assert!(!actual);
+
+        Ok(())
+    }
+
+    /// Tests a renewed lease outlives the TTL it was acquired with, and an expired one is not renewed.
+    #[test]
+    fn semaphore_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let ttl = Duration::from_secs(1);
+        let lease = client
+            .acquire_lease("test17", "report", "andy", 1, ttl)?
+            .expect("the lease should be acquired");
+
+        // act && assert
+        clock.advance(Duration::from_millis(800));
+        assert!(client.renew_lease("test17", "report", "andy", &lease.id, ttl)?);
+
+        clock.advance(Duration::from_millis(800));
+        let actual = client.acquire_lease("test17", "report", "andy", 1, ttl)?;
+        assert_eq!(actual, None);
+
+        // expired
+        clock.advance(Duration::from_millis(200));
+        assert!(!client.renew_lease("test17", "report", "andy", &lease.id, ttl)?);
+
+        let actual = client.acquire_lease("test17", "report", "andy", 1, ttl)?;
+        assert!(actual.is_some());
+
+        Ok(())
+    }
+
+    /// Tests the concurrent holders never exceed the limit.
+    #[test]
+    fn semaphore_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let barrier = Arc::new(std::sync::Barrier::new(8));
+
+        // act
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || -> Result<usize, RateLimitError> {
+                    let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+                    barrier.wait();
+                    let mut acquired = 0;
+                    for _ in 0..10 {
+                        let lease = client.acquire_lease(
+                            "test17",
+                            "report",
+                            "andy",
+                            5,
+                            Duration::from_secs(60),
+                        )?;
+                        if lease.is_some() {
+                            acquired += 1;
+                        }
+                    }
+                    Ok(acquired)
+                })
+            })
+            .collect();
+        let mut acquired = 0;
+        for worker in workers {
+            acquired += worker.join().expect("the worker should not panic")?;
+        }
+
+        // assert
+        assert_eq!(acquired, 5);
+
+        Ok(())
+    }
+
+    /// Tests the error when the limit or the TTL of the leases is zero.
+    #[test]
+    fn semaphore_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+
+        // act && assert
+        let actual = client.acquire_lease("test17", "report", "andy", 0, Duration::from_secs(1));
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        let actual = client.acquire_lease("test17", "report", "andy", 1, Duration::ZERO);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        let actual = client.renew_lease("test17", "report", "andy", "0:1", Duration::ZERO);
+        assert!(matches!(actual, Err(RateLimitError::InvalidConfig(_))));
+
+        Ok(())
+    }
+
+    /// Tests the leases are numbered by a sequence of their own, which a short lease never
+    /// expires before a longer one.
+    #[test]
+    fn semaphore_redis_case6() -> Result<(), RateLimitError> {
+        // prev
+        let mut conn = initialize_redis()?;
+
+        // arrange
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.acquire_lease("test17", "report", "andy", 2, Duration::from_secs(60))?;
+
+        // act
+        client.acquire_lease("test17", "report", "andy", 2, Duration::from_millis(10))?;
+
+        // assert
+        let ttl: i64 = redis::cmd("PTTL")
+            .arg("test17:report:{andy}:lease_seq")
+            .query(&mut conn)?;
+        assert!(ttl > 10_000, "ttl {ttl}");
+
+        let exists: bool = redis::cmd("EXISTS")
+            .arg("test17:report:{andy}:seq")
+            .query(&mut conn)?;
+        assert!(!exists);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+// NOTE: docker-compose --profile sentinel up -d && cargo test --all --all-features -- --test-threads 1
+#![cfg(feature = "sentinel")]
+
+const SENTINELS: [&str; 3] = [
+    "redis://127.0.0.1:26379/",
+    "redis://127.0.0.1:26380/",
+    "redis://127.0.0.1:26381/",
+];
+const MASTER_NAME: &str = "mymaster";
+
+/// Returns the address of the current master, as known by the first sentinel.
+fn master_addr() -> redis::RedisResult<(String, u16)> {
+    let client = redis::Client::open(SENTINELS[0])?;
+    let mut conn = client.get_connection()?;
+
+    redis::cmd("SENTINEL")
+        .arg("GET-MASTER-ADDR-BY-NAME")
+        .arg(MASTER_NAME)
+        .query(&mut conn)
+}
+
+fn initialize_redis() -> redis::RedisResult<()> {
+    let (host, port) = master_addr()?;
+    let client = redis::Client::open(format!("redis://{host}:{port}/"))?;
+    let mut conn = client.get_connection()?;
+
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redis::ConnectionLike;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis::RateLimiterRedisSentinel;
+    use rrr::rule::Rule;
+    use rrr::sentinel::SentinelConfig;
+    use std::time::{Duration, Instant};
+
+    /// Returns the role of the node the connection is on, e.g. "master" or "slave".
+    fn role(conn: &mut impl ConnectionLike) -> redis::RedisResult<String> {
+        let reply: Vec<redis::Value> = redis::cmd("ROLE").query(conn)?;
+
+        redis::from_redis_value(&reply[0])
+    }
+
+    /// Integration: Initiated -> Throttled -> Reset -> Refilled, on the master found by Sentinel.
+    #[test]
+    fn sentinel_redis_case1() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(1));
+        let mut client = RateLimiterRedisSentinel::open_sentinel(&SENTINELS, MASTER_NAME)?;
+        let key_prefix = "test13";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        assert_eq!(role(&mut client.conn)?, "master");
+        assert!(client.replica.is_none());
+
+        let actual = client.record_sliding_window(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        // throttled
+        let actual = client.record_sliding_window(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+
+        // reset
+        client.reset_sliding_window(key_prefix, resource, subject, &rule)?;
+
+        // refilled
+        let actual = client.record_sliding_window(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests the fetch calls are sent to a replica, which sees the requests recorded on the master.
+    #[test]
+    fn sentinel_redis_case2() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(2, Duration::from_secs(60));
+        let config = SentinelConfig {
+            read_from_replicas: true,
+            ..SentinelConfig::default()
+        };
+        let mut client =
+            RateLimiterRedisSentinel::open_sentinel_with_config(&SENTINELS, MASTER_NAME, config)?;
+        let key_prefix = "test13";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let replica = client.replica.as_mut().expect("a replica connection");
+        assert_eq!(role(replica)?, "slave");
+
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 1);
+
+        // wait until the replica has the recorded request
+        let _: u64 = redis::cmd("WAIT")
+            .arg(1)
+            .arg(1000)
+            .query(&mut client.conn)?;
+
+        let actual = client.fetch_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 1);
+
+        Ok(())
+    }
+
+    /// Tests an unknown master can not be discovered.
+    #[test]
+    fn sentinel_redis_case3() {
+        // act
+        let actual = RateLimiterRedisSentinel::open_sentinel(&SENTINELS, "unknown");
+
+        // assert
+        assert!(matches!(actual, Err(RateLimitError::Connection(_))));
+    }
+
+    /// Integration: Recorded -> Failover -> Recorded on the promoted master.
+    #[test]
+    fn sentinel_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(60));
+        let mut client = RateLimiterRedisSentinel::open_sentinel(&SENTINELS, MASTER_NAME)?;
+        let key_prefix = "test13";
+        let resource = "data";
+        let subject = "andy";
+
+        let actual = client.record_fixed_window(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        // act
+        let old_master = master_addr()?;
+        let sentinel = redis::Client::open(SENTINELS[0])?;
+        redis::cmd("SENTINEL")
+            .arg("FAILOVER")
+            .arg(MASTER_NAME)
+            .query::<()>(&mut sentinel.get_connection()?)?;
+
+        let started = Instant::now();
+        while master_addr()? == old_master {
+            assert!(started.elapsed() < Duration::from_secs(30), "no failover");
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        // the old master is demoted once the replica has been promoted
+        std::thread::sleep(Duration::from_secs(1));
+
+        // assert
+        let actual = client.record_fixed_window(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(role(&mut client.conn)?, "master");
+
+        Ok(())
+    }
+}
@@ -1,17 +1,10 @@
 // NOTE: cargo test --all -- --test-threads 1
-fn initialize_redis() -> Result<(), ()> {
+fn initialize_redis() -> redis::RedisResult<()> {
     let redis_address: &str = "redis://127.0.0.1:6379/";
-    let client = redis::Client::open(redis_address).map_err(|err| {
-        eprintln!("Error: could not open the connection to the Redis({redis_address}): {err}")
-    })?;
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
 
-    let mut conn = client.get_connection().map_err(|err| {
-        eprintln!("Error: client could not get the connection to the Redis: {err}")
-    })?;
-
-    let _: String = redis::cmd("FLUSHALL")
-        .query(&mut conn)
-        .map_err(|err| eprintln!("Error: could not flush all data in Redis: {err}"))?;
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
 
     Ok(())
 }
@@ -19,74 +12,207 @@ fn initialize_redis() -> Result<(), ()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rrr::rate_limiter_redis;
-    use std::time::Duration;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::{self, Duration};
 
     const CONN: &str = "redis://127.0.0.1:6379/";
 
     /// Tests the requests exceed the rate limit.
     #[test]
-    fn sliding_log_redis_case1() -> Result<(), ()> {
+    fn sliding_log_redis_case1() -> Result<(), RateLimitError> {
         // prev
         initialize_redis()?;
 
         // arrange
         let limit_count = 1;
-        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN, limit_count)?;
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
         let key_prefix = "test2";
         let resource = "data";
         let subject = "andy";
         let size = Duration::from_secs(1);
+        let rule = Rule::new(limit_count, size);
 
         // act && assert
-        let actual = client.record_sliding_log(key_prefix, resource, subject, size)?;
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
 
-        assert!(actual);
+        assert!(actual.allowed);
 
-        let actual = client.record_sliding_log(key_prefix, resource, subject, size)?;
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
 
-        assert!(!actual);
+        assert!(!actual.allowed);
 
         Ok(())
     }
 
     /// Integration: Initiated -> Throttled -> Cool Down -> Refilled
     #[test]
-    fn sliding_log_redis_case2() -> Result<(), ()> {
+    fn sliding_log_redis_case2() -> Result<(), RateLimitError> {
         // prev
         initialize_redis()?;
 
         // arrange
         let limit_count = 1;
-        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN, limit_count)?;
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
         let key_prefix = "test2";
         let resource = "data";
         let subject = "andy";
         let size = Duration::from_secs(1);
+        let rule = Rule::new(limit_count, size);
 
         // act && assert
-        let actual = client.record_sliding_log(key_prefix, resource, subject, size)?;
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
 
-        assert!(actual);
+        assert!(actual.allowed);
 
         // throttled
-        let actual = client.record_sliding_log(key_prefix, resource, subject, size)?;
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
 
-        assert!(!actual);
+        assert!(!actual.allowed);
 
         // cool down
-        std::thread::sleep(Duration::from_secs(2));
+        clock.advance(Duration::from_secs(2));
 
-        let count = client.fetch_sliding_log(key_prefix, resource, subject)?;
-        assert_eq!(count, 0);
+        let actual = client.fetch_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 1);
 
         // refilled
-        let actual = client.record_sliding_log(key_prefix, resource, subject, size)?;
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+
+        assert!(actual.allowed);
+
+        let actual = client.fetch_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests the decision tells the remaining requests and how long to wait when throttled.
+    #[test]
+    fn sliding_log_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let limit_count = 2;
+        let size = Duration::from_secs(1);
+        let rule = Rule::new(limit_count, size);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test2";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.limit, 2);
+        assert_eq!(actual.remaining, 1);
+        assert_eq!(actual.retry_after, Duration::ZERO);
+
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        // throttled
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.remaining, 0);
+        assert_eq!(actual.retry_after, Duration::from_secs(1));
+        assert_eq!(
+            actual.reset_at,
+            time::UNIX_EPOCH + Duration::from_secs(1_700_000_001)
+        );
+
+        // retry after
+        clock.advance(actual.retry_after);
+
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests a fractional window is not truncated to whole seconds.
+    #[test]
+    fn sliding_log_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let limit_count = 1;
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test2";
+        let resource = "data";
+        let subject = "andy";
+        let size = Duration::from_millis(1500);
+        let rule = Rule::new(limit_count, size);
+
+        // act && assert
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        // throttled
+        clock.advance(Duration::from_secs(1));
+
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_millis(500));
+
+        // cool down
+        clock.advance(actual.retry_after);
+
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests the weighted requests consume their cost, and are denied when less than it remains.
+    #[test]
+    fn sliding_log_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
 
-        assert!(actual);
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(60));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test2";
+        let resource = "data";
+        let subject = "andy";
 
-        let count = client.fetch_sliding_log(key_prefix, resource, subject)?;
-        assert_eq!(count, 1);
+        // act && assert
+        let actual = client.record_sliding_log_weighted(key_prefix, resource, subject, &rule, 4)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 6);
+
+        let actual = client.record_sliding_log_weighted(key_prefix, resource, subject, &rule, 4)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 2);
+
+        // less than the cost remains
+        let actual = client.record_sliding_log_weighted(key_prefix, resource, subject, &rule, 3)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.remaining, 2);
+        assert!(actual.retry_after > Duration::ZERO);
+
+        let actual = client.record_sliding_log_weighted(key_prefix, resource, subject, &rule, 2)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        let actual = client.record_sliding_log(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
 
         Ok(())
     }
@@ -1,17 +1,10 @@
 // NOTE: cargo test --all -- --test-threads 1
-fn initialize_redis() -> Result<(), ()> {
+fn initialize_redis() -> redis::RedisResult<()> {
     let redis_address: &str = "redis://127.0.0.1:6379/";
-    let client = redis::Client::open(redis_address).map_err(|err| {
-        eprintln!("Error: could not open the connection to the Redis({redis_address}): {err}")
-    })?;
+    let client = redis::Client::open(redis_address)?;
+    let mut conn = client.get_connection()?;
 
-    let mut conn = client.get_connection().map_err(|err| {
-        eprintln!("Error: client could not get the connection to the Redis: {err}")
-    })?;
-
-    let _: String = redis::cmd("FLUSHALL")
-        .query(&mut conn)
-        .map_err(|err| eprintln!("Error: could not flush all data in Redis: {err}"))?;
+    let _: String = redis::cmd("FLUSHALL").query(&mut conn)?;
 
     Ok(())
 }
@@ -19,69 +12,261 @@ fn initialize_redis() -> Result<(), ()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rrr::rate_limiter_redis;
-    use std::time::Duration;
+    use rrr::clock::MockClock;
+    use rrr::error::RateLimitError;
+    use rrr::rate_limiter_redis::{self, TimeSource};
+    use rrr::rule::Rule;
+    use std::sync::Arc;
+    use std::time::{self, Duration};
 
     const CONN: &str = "redis://127.0.0.1:6379/";
 
     /// Tests the requests exceed the rate limit.
     #[test]
-    fn token_bucket_redis_case1() -> Result<(), ()> {
+    fn token_bucket_redis_case1() -> Result<(), RateLimitError> {
         // prev
         initialize_redis()?;
 
         // arrange
         let limit_count = 1;
         let size = Duration::from_secs(1);
-        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN, limit_count)?;
+        let rule = Rule::new(limit_count, size);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
         let key_prefix = "test5";
         let resource = "data";
         let subject = "andy";
 
         // act
-        let actual = client.record_token_bucket(key_prefix, resource, subject, size)?;
-        assert!(actual);
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
 
-        let actual = client.record_token_bucket(key_prefix, resource, subject, size)?;
-        assert!(!actual);
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
 
         Ok(())
     }
 
     /// Integration: Initiated -> Throttled -> Cool Down -> Refilled
     #[test]
-    fn token_bucket_redis_case2() -> Result<(), ()> {
+    fn token_bucket_redis_case2() -> Result<(), RateLimitError> {
         // prev
         initialize_redis()?;
 
         // arrange
         let limit_count = 1;
         let size = Duration::from_secs(1);
-        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN, limit_count)?;
+        let rule = Rule::new(limit_count, size);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
         let key_prefix = "test5";
         let resource = "data";
         let subject = "andy";
 
         // act && assert
-        let actual = client.record_token_bucket(key_prefix, resource, subject, size)?;
-        assert!(actual);
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
 
         // throttled
-        let actual = client.record_token_bucket(key_prefix, resource, subject, size)?;
-        assert!(!actual);
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
 
         // cool down
-        std::thread::sleep(Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
 
-        let count = client.fetch_token_bucket(key_prefix, resource, subject, size)?;
-        assert_eq!(count, 1);
+        let actual = client.fetch_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 1);
 
         // refilled
-        let actual = client.record_token_bucket(key_prefix, resource, subject, size)?;
-        assert!(actual);
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        let actual = client.fetch_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests the burst of the rule sets the capacity of the bucket.
+    #[test]
+    fn token_bucket_redis_case3() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(1)).with_burst(3);
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let key_prefix = "test5";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        for _ in 0..3 {
+            let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+            assert!(actual.allowed);
+            assert_eq!(actual.limit, 3);
+        }
+
+        // throttled
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests a sub-second refill period.
+    #[test]
+    fn token_bucket_redis_case4() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_millis(200));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test5";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        // throttled
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.retry_after, Duration::from_millis(200));
+
+        // refilled
+        clock.advance(actual.retry_after);
+
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests the tokens accrue continuously instead of refilling the whole bucket at once.
+    #[test]
+    fn token_bucket_redis_case5() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(1));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test5";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let mut actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        while actual.allowed {
+            actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        }
+
+        // throttled, one token accrues every 100ms
+        assert_eq!(actual.retry_after, Duration::from_millis(100));
+        assert_eq!(
+            actual.reset_at,
+            time::UNIX_EPOCH + Duration::from_secs(1_700_000_001)
+        );
+
+        // refilling
+        clock.advance(Duration::from_millis(350));
+
+        let actual = client.fetch_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert_eq!(actual.remaining, 3);
+
+        for _ in 0..3 {
+            let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+            assert!(actual.allowed);
+        }
+
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        assert!(!actual.allowed);
+
+        Ok(())
+    }
+
+    /// Tests the weighted requests wait until the bucket has room for their cost, with a mock clock.
+    #[test]
+    fn token_bucket_redis_case6() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(10, Duration::from_secs(1));
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let key_prefix = "test5";
+        let resource = "data";
+        let subject = "andy";
+
+        // act && assert
+        let actual =
+            client.record_token_bucket_weighted(key_prefix, resource, subject, &rule, 6)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 4);
+
+        // less than the cost remains
+        let actual =
+            client.record_token_bucket_weighted(key_prefix, resource, subject, &rule, 5)?;
+        assert!(!actual.allowed);
+        assert_eq!(actual.remaining, 4);
+        assert_eq!(actual.retry_after, Duration::from_millis(100));
+
+        clock.advance(actual.retry_after);
+        let actual =
+            client.record_token_bucket_weighted(key_prefix, resource, subject, &rule, 5)?;
+        assert!(actual.allowed);
+        assert_eq!(actual.remaining, 0);
+
+        Ok(())
+    }
+
+    /// Tests no token remains for the denied requests once the grant the bucket was drawn down
+    /// with is gone.
+    #[test]
+    fn token_bucket_redis_case7() -> Result<(), RateLimitError> {
+        // prev
+        initialize_redis()?;
+
+        // arrange
+        let rule = Rule::new(1, Duration::from_secs(60)).with_burst(2);
+        let clock = MockClock::new(Duration::from_secs(1_700_000_000));
+        let mut client = rate_limiter_redis::RateLimiterRedis::open(CONN)?;
+        client.time_source = TimeSource::Client;
+        client.clock = Arc::new(clock.clone());
+        let mut conn = redis::Client::open(CONN)?.get_connection()?;
+        let key_prefix = "test5";
+        let resource = "data";
+        let subject = "andy";
+        client.grant_extra_quota(key_prefix, resource, subject, 5, Duration::from_secs(60))?;
+        for _ in 0..7 {
+            let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+            assert!(actual.allowed);
+        }
+
+        // act, the grant expires
+        let _: u64 = redis::cmd("DEL")
+            .arg("test5:data:{andy}:grant")
+            .query(&mut conn)?;
+        let actual = client.record_token_bucket(key_prefix, resource, subject, &rule)?;
+        let fetched = client.fetch_token_bucket(key_prefix, resource, subject, &rule)?;
 
-        let count = client.fetch_token_bucket(key_prefix, resource, subject, size)?;
-        assert_eq!(count, 0);
+        // assert
+        assert!(!actual.allowed);
+        assert_eq!(actual.remaining, 0);
+        assert_eq!(fetched.remaining, 0);
 
         Ok(())
     }